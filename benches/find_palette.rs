@@ -17,16 +17,41 @@ fn get_image_buffer(img: image::DynamicImage) -> Vec<u8> {
 }
 
 fn q1(bencher: &mut Bencher) {
-    let img = image::open(&Path::new("images/photo1.jpg")).unwrap();
+    let img = image::open(Path::new("images/photo1.jpg")).unwrap();
     let pixels = get_image_buffer(img);
     bencher.iter(|| color_thief::get_palette(&pixels, ColorFormat::Rgb, 1, 10))
 }
 
 fn q10(bencher: &mut Bencher) {
-    let img = image::open(&Path::new("images/photo1.jpg")).unwrap();
+    let img = image::open(Path::new("images/photo1.jpg")).unwrap();
     let pixels = get_image_buffer(img);
     bencher.iter(|| color_thief::get_palette(&pixels, ColorFormat::Rgb, 10, 10))
 }
 
-benchmark_group!(benches, q1, q10);
+fn q10_two_colors(bencher: &mut Bencher) {
+    let img = image::open(Path::new("images/photo1.jpg")).unwrap();
+    let pixels = get_image_buffer(img);
+    bencher.iter(|| color_thief::get_palette(&pixels, ColorFormat::Rgb, 10, 2))
+}
+
+fn quantize_two_colors(bencher: &mut Bencher) {
+    let img = image::open(Path::new("images/photo1.jpg")).unwrap();
+    let pixels = get_image_buffer(img);
+    let mut histogram = color_thief::Histogram::new();
+    histogram.add_frame(&pixels, ColorFormat::Rgb, 10).unwrap();
+    bencher.iter(|| histogram.quantize(2))
+}
+
+// Runs the SIMD path with the `simd` feature.
+fn histogram_q1(bencher: &mut Bencher) {
+    let img = image::open(Path::new("images/photo1.jpg")).unwrap();
+    let pixels = get_image_buffer(img);
+    let mut histogram = color_thief::Histogram::new();
+    bencher.iter(|| {
+        histogram.clear();
+        histogram.add_frame(&pixels, ColorFormat::Rgb, 1)
+    })
+}
+
+benchmark_group!(benches, q1, q10, q10_two_colors, quantize_two_colors, histogram_q1);
 benchmark_main!(benches);
@@ -0,0 +1,74 @@
+#![no_main]
+
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate color_thief;
+
+use color_thief::{ColorFormat, HistogramBuilder, Options, Quantizer, Rect, Sampling};
+
+const FORMATS: &[ColorFormat] = &[
+    ColorFormat::Rgb,
+    ColorFormat::Rgba,
+    ColorFormat::Argb,
+    ColorFormat::Abgr,
+    ColorFormat::Bgr,
+    ColorFormat::Bgra,
+    ColorFormat::Rgbx,
+    ColorFormat::Bgrx,
+    ColorFormat::Gray,
+    ColorFormat::GrayAlpha,
+    ColorFormat::RgbaPremultiplied,
+    ColorFormat::Cmyk,
+    ColorFormat::YCbCr,
+];
+
+// The first 8 bytes are parameters, the rest are pixels.
+// Errors are fine, panics are not.
+fuzz_target!(|data: &[u8]| {
+    if data.len() < 8 {
+        return;
+    }
+
+    let color_format = FORMATS[data[0] as usize % FORMATS.len()];
+    let quality = data[1] % 12;
+    let max_colors = data[2];
+    let width = data[3] as usize;
+    let pixels = &data[8..];
+
+    let _ = color_thief::get_palette(pixels, color_format, quality, max_colors);
+    let _ = color_thief::get_indexed(pixels, color_format, quality, max_colors);
+    let _ = color_thief::get_palette_with_stride(pixels, color_format, width, width * 3 + 1, quality, max_colors);
+    let _ = color_thief::get_palette_region(pixels, color_format, width, width, Rect::new(1, 1, width, width),
+                                            quality, max_colors);
+    let _ = color_thief::get_background_color(pixels, color_format, width, width, quality);
+    let _ = color_thief::is_grayscale(pixels, color_format, quality, max_colors);
+
+    // Invalid values are included on purpose.
+    let options = Options {
+        signal_bits: data[4] % 10,
+        saturation_weight: data[5] as f32 * 8.0,
+        sampling: match data[6] % 3 {
+            0 => Sampling::Stride,
+            1 => Sampling::Random { seed: data[6] as u64 },
+            _ => Sampling::Downscale { max_dim: data[6] as u32 / 3 },
+        },
+        ..Options::default()
+    };
+
+    let quantizer = Quantizer::new().quality(quality).max_colors(max_colors).options(options);
+    let _ = quantizer.build_palette(pixels, color_format);
+    let height = if width == 0 { 0 } else { pixels.len() / color_format.bytes_per_pixel() / width };
+    let _ = quantizer.build_palette_with_size(pixels, color_format, width, height);
+
+    // Chunks do not have to end on a pixel boundary.
+    if let Ok(builder) = HistogramBuilder::with_options(options) {
+        let mut builder = builder.quality(quality);
+        for chunk in pixels.chunks(data[7] as usize + 1) {
+            if builder.push_pixels(chunk, color_format).is_err() {
+                break;
+            }
+        }
+
+        let _ = builder.finish().quantize(max_colors);
+    }
+});
@@ -0,0 +1,412 @@
+// Copyright 2017, Reizner Evgeniy <razrfalcon@gmail.com>.
+// See the COPYRIGHT file at the top-level directory of this distribution.
+// Licensed under the MIT license, see the LICENSE file or <http://opensource.org/licenses/MIT>
+
+//! Color conversion helpers.
+
+use core::cmp;
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use Color;
+
+/// Converts a color into HSL.
+///
+/// Returns hue in degrees (0..360), saturation (0..1) and lightness (0..1).
+pub fn to_hsl(c: &Color) -> (f32, f32, f32) {
+    let (max, min) = max_min(c);
+    let l = (max + min) / 2.0;
+
+    let delta = max - min;
+    let s = if delta == 0.0 {
+        0.0
+    } else if l <= 0.5 {
+        delta / (max + min)
+    } else {
+        delta / (2.0 - max - min)
+    };
+
+    (hue(*c), s, l)
+}
+
+/// Converts a color into HSV.
+///
+/// Returns hue in degrees (0..360), saturation (0..1) and value (0..1).
+pub fn to_hsv(c: &Color) -> (f32, f32, f32) {
+    let (max, min) = max_min(c);
+    let s = if max == 0.0 { 0.0 } else { (max - min) / max };
+    (hue(*c), s, max)
+}
+
+/// Returns the max and min channels in a 0..1 range.
+fn max_min(c: &Color) -> (f32, f32) {
+    let max = c.r.max(c.g).max(c.b);
+    let min = c.r.min(c.g).min(c.b);
+    (max as f32 / 255.0, min as f32 / 255.0)
+}
+
+/// Returns a hue of a color in degrees, 0..360.
+///
+/// Gray colors have a zero hue.
+pub(crate) fn hue(c: Color) -> f32 {
+    let r = c.r as f32;
+    let g = c.g as f32;
+    let b = c.b as f32;
+
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let delta = max - min;
+
+    if delta == 0.0 {
+        return 0.0;
+    }
+
+    let h = if max == r {
+        (g - b) / delta
+    } else if max == g {
+        (b - r) / delta + 2.0
+    } else {
+        (r - g) / delta + 4.0
+    };
+
+    let h = h * 60.0;
+    if h < 0.0 { h + 360.0 } else { h }
+}
+
+/// Returns how warm a hue is, in a -1..1 range.
+///
+/// Orange (30 degrees) is 1, blue (210 degrees) is -1 and the change in between is linear,
+/// since trigonometry is not available in `no_std`.
+pub(crate) fn hue_warmth(hue: f32) -> f32 {
+    let distance = (hue - 30.0).abs() % 360.0;
+    let distance = if distance > 180.0 { 360.0 - distance } else { distance };
+    1.0 - distance / 90.0
+}
+
+/// Converts an sRGB channel value into a linear light (0..1).
+pub fn srgb_to_linear(c: u8) -> f32 {
+    SRGB_TO_LINEAR[c as usize]
+}
+
+/// Converts a linear light (0..1) into the closest sRGB channel value.
+pub fn linear_to_srgb(v: f32) -> u8 {
+    // The table is sorted, so we can find the closest value without `powf`,
+    // which is not available in `no_std`.
+    let i = SRGB_TO_LINEAR.partition_point(|&x| x < v);
+    if i == 0 {
+        0
+    } else if i == SRGB_TO_LINEAR.len() {
+        255
+    } else if v - SRGB_TO_LINEAR[i - 1] < SRGB_TO_LINEAR[i] - v {
+        (i - 1) as u8
+    } else {
+        i as u8
+    }
+}
+
+/// Converts a color into CIE Lab with a D65 white point.
+///
+/// Returns lightness (0..100) and a, b components (roughly -128..127).
+pub fn to_lab(c: &Color) -> (f32, f32, f32) {
+    let r = srgb_to_linear(c.r);
+    let g = srgb_to_linear(c.g);
+    let b = srgb_to_linear(c.b);
+
+    let x = lab_f((0.4124564 * r + 0.3575761 * g + 0.1804375 * b) / WHITE_X);
+    let y = lab_f(0.2126729 * r + 0.7151522 * g + 0.072175 * b);
+    let z = lab_f((0.0193339 * r + 0.119192 * g + 0.9503041 * b) / WHITE_Z);
+
+    (116.0 * y - 16.0, 500.0 * (x - y), 200.0 * (y - z))
+}
+
+/// Converts CIE Lab with a D65 white point into the closest sRGB color.
+///
+/// Out of gamut colors are clamped.
+pub fn from_lab(l: f32, a: f32, b: f32) -> Color {
+    let y = (l + 16.0) / 116.0;
+    let x = lab_f_inv(y + a / 500.0) * WHITE_X;
+    let z = lab_f_inv(y - b / 200.0) * WHITE_Z;
+    let y = lab_f_inv(y);
+
+    Color::new(
+        linear_to_srgb( 3.2404542 * x - 1.5371385 * y - 0.4985314 * z),
+        linear_to_srgb(-0.969266  * x + 1.8760108 * y + 0.041556  * z),
+        linear_to_srgb( 0.0556434 * x - 0.2040259 * y + 1.0572252 * z),
+    )
+}
+
+/// Returns `steps` colors of a smooth gradient through palette colors.
+///
+/// Palette colors are ordered by relative luminance, from dark to light,
+/// and interpolated in a linear light to avoid muddy midpoints.
+/// The first and the last colors are the darkest and the lightest palette colors.
+pub fn gradient(palette: &[Color], steps: usize) -> Vec<Color> {
+    let mut colors = palette.to_vec();
+    colors.sort_by(|a, b| {
+        relative_luminance(*a).partial_cmp(&relative_luminance(*b)).unwrap_or(cmp::Ordering::Equal)
+    });
+
+    if colors.len() < 2 {
+        return colors.first().map(|c| vec![*c; steps]).unwrap_or_default();
+    }
+
+    let segments = (colors.len() - 1) as f32;
+    let mix = |a: u8, b: u8, t: f32| {
+        linear_to_srgb(srgb_to_linear(a) * (1.0 - t) + srgb_to_linear(b) * t)
+    };
+
+    (0..steps).map(|i| {
+        let pos = if steps == 1 { 0.0 } else { i as f32 / (steps - 1) as f32 * segments };
+        let n = cmp::min(pos as usize, colors.len() - 2);
+        let (a, b, t) = (colors[n], colors[n + 1], pos - n as f32);
+        Color::new(mix(a.r, b.r, t), mix(a.g, b.g, t), mix(a.b, b.b, t))
+    }).collect()
+}
+
+/// Returns the mean of palette colors.
+///
+/// Colors are averaged in a linear light to avoid muddy results.
+///
+/// Returns `None` for an empty palette.
+pub fn average_color(palette: &[Color]) -> Option<Color> {
+    let colors: Vec<_> = palette.iter().map(|c| (*c, 1)).collect();
+    average_color_weighted(&colors)
+}
+
+/// Returns the mean of palette colors weighted by their counts.
+///
+/// Accepts the output of `get_palette_with_counts`.
+///
+/// Returns `None` for an empty palette or when all counts are zero.
+pub fn average_color_weighted(palette: &[(Color, u64)]) -> Option<Color> {
+    let total: f64 = palette.iter().map(|c| c.1 as f64).sum();
+    if total == 0.0 {
+        return None;
+    }
+
+    let mean = |f: fn(&Color) -> u8| {
+        let sum: f64 = palette.iter().map(|c| srgb_to_linear(f(&c.0)) as f64 * c.1 as f64).sum();
+        linear_to_srgb((sum / total) as f32)
+    };
+
+    Some(Color::new(mean(|c| c.r), mean(|c| c.g), mean(|c| c.b)))
+}
+
+/// Returns the Shannon entropy of palette colors population, in bits.
+///
+/// Accepts the output of `get_palette_with_counts`.
+/// A single color has a zero entropy and N equally populated colors have `log2(N)`,
+/// so the higher the value, the more colorful an image is.
+///
+/// Colors with a zero count are ignored. Returns 0 for an empty palette.
+pub fn palette_entropy(palette: &[(Color, u64)]) -> f32 {
+    let total: f64 = palette.iter().map(|c| c.1 as f64).sum();
+    if total == 0.0 {
+        return 0.0;
+    }
+
+    let entropy: f64 = palette.iter().filter(|c| c.1 != 0).map(|c| {
+        let p = c.1 as f64 / total;
+        -p * log2(p)
+    }).sum();
+
+    entropy as f32
+}
+
+const WHITE_X: f32 = 0.95047;
+const WHITE_Z: f32 = 1.08883;
+const LAB_DELTA: f32 = 6.0 / 29.0;
+
+fn lab_f(t: f32) -> f32 {
+    if t > LAB_DELTA * LAB_DELTA * LAB_DELTA {
+        cbrt(t)
+    } else {
+        t / (3.0 * LAB_DELTA * LAB_DELTA) + 4.0 / 29.0
+    }
+}
+
+fn lab_f_inv(t: f32) -> f32 {
+    if t > LAB_DELTA {
+        t * t * t
+    } else {
+        3.0 * LAB_DELTA * LAB_DELTA * (t - 4.0 / 29.0)
+    }
+}
+
+/// A cube root of a positive number.
+///
+/// `cbrt` is not available in `no_std`, so we use a bit hack approximation
+/// refined by Newton's method.
+fn cbrt(x: f32) -> f32 {
+    let mut y = f32::from_bits(x.to_bits() / 3 + 709_921_077);
+    for _ in 0..3 {
+        y = (2.0 * y + x / (y * y)) / 3.0;
+    }
+
+    y
+}
+
+/// A square root of a positive number.
+///
+/// `sqrt` is not available in `no_std`, so we use Newton's method.
+pub(crate) fn sqrt(x: f32) -> f32 {
+    if x <= 0.0 {
+        return 0.0;
+    }
+
+    // Halving the exponent is a good enough initial guess.
+    let mut y = f32::from_bits((x.to_bits() >> 1) + 532_487_669);
+    for _ in 0..4 {
+        y = (y + x / y) / 2.0;
+    }
+
+    y
+}
+
+/// A binary logarithm of a positive number.
+///
+/// `log2` is not available in `no_std`, so we split a number into an exponent
+/// and a mantissa and use the `atanh` series for the latter.
+fn log2(x: f64) -> f64 {
+    let bits = x.to_bits();
+    let exponent = ((bits >> 52) & 0x7ff) as i64 - 1023;
+    let mantissa = f64::from_bits((bits & 0x000f_ffff_ffff_ffff) | (1023 << 52));
+
+    // ln(m) = 2 * atanh((m - 1) / (m + 1)), where m is in a 1..2 range.
+    let z = (mantissa - 1.0) / (mantissa + 1.0);
+    let z2 = z * z;
+    let mut term = z;
+    let mut ln = 0.0;
+    for n in 0..10 {
+        ln += term / (2 * n + 1) as f64;
+        term *= z2;
+    }
+
+    exponent as f64 + 2.0 * ln / core::f64::consts::LN_2
+}
+
+/// Converts a color into normalized 0..1 floats.
+pub fn to_f32(c: &Color) -> [f32; 3] {
+    [c.r as f32 / 255.0, c.g as f32 / 255.0, c.b as f32 / 255.0]
+}
+
+/// Formats a color as `#rrggbb`.
+pub fn to_hex(c: &Color) -> String {
+    format!("#{:02x}{:02x}{:02x}", c.r, c.g, c.b)
+}
+
+/// Parses a color in a `#rgb` or `#rrggbb` format.
+///
+/// `#` is optional. Returns `None` on invalid strings.
+pub fn from_hex(text: &str) -> Option<Color> {
+    let text = text.strip_prefix('#').unwrap_or(text);
+    if !text.bytes().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    let channel = |i: usize, len: usize| u8::from_str_radix(&text[i * len..(i + 1) * len], 16).ok();
+    match text.len() {
+        3 => Some(Color::new(channel(0, 1)? * 17, channel(1, 1)? * 17, channel(2, 1)? * 17)),
+        6 => Some(Color::new(channel(0, 2)?, channel(1, 2)?, channel(2, 2)?)),
+        _ => None,
+    }
+}
+
+/// Returns a WCAG relative luminance of a color (0..1).
+pub fn relative_luminance(c: Color) -> f32 {
+      0.2126 * srgb_to_linear(c.r)
+    + 0.7152 * srgb_to_linear(c.g)
+    + 0.0722 * srgb_to_linear(c.b)
+}
+
+/// Returns a WCAG contrast ratio of two colors (1..21).
+///
+/// The order of colors doesn't matter.
+pub fn contrast_ratio(a: Color, b: Color) -> f32 {
+    let l1 = relative_luminance(a);
+    let l2 = relative_luminance(b);
+    (l1.max(l2) + 0.05) / (l1.min(l2) + 0.05)
+}
+
+/// Returns black or white, whichever has a better contrast with the background.
+pub fn best_text_color(background: Color) -> Color {
+    let black = Color::new(0, 0, 0);
+    let white = Color::new(255, 255, 255);
+    if contrast_ratio(background, black) >= contrast_ratio(background, white) {
+        black
+    } else {
+        white
+    }
+}
+
+/// Returns a palette color that stands out the most from the dominant one.
+///
+/// The dominant color is the first one, just like in `get_dominant_color`.
+/// Colors are scored by a contrast ratio and by a hue distance.
+/// The hue distance is scaled by the lowest saturation of both colors,
+/// since gray colors have no meaningful hue.
+///
+/// Returns `None` for palettes with less than two colors.
+pub fn accent_color(palette: &[Color]) -> Option<Color> {
+    let dominant = *palette.first()?;
+    let (dominant_hue, dominant_saturation, _) = to_hsl(&dominant);
+
+    let score = |c: &Color| {
+        let (hue, saturation, _) = to_hsl(c);
+        let mut distance = (hue - dominant_hue).abs();
+        if distance > 180.0 {
+            distance = 360.0 - distance;
+        }
+
+        // Both parts are in a 0..1 range.
+        let hue_score = distance / 180.0 * saturation.min(dominant_saturation);
+        let contrast_score = (contrast_ratio(dominant, *c) - 1.0) / 20.0;
+        hue_score + contrast_score
+    };
+
+    let mut best: Option<(Color, f32)> = None;
+    for c in &palette[1..] {
+        let s = score(c);
+        if best.is_none_or(|(_, best_score)| s > best_score) {
+            best = Some((*c, s));
+        }
+    }
+
+    best.map(|(c, _)| c)
+}
+
+static SRGB_TO_LINEAR: [f32; 256] = [
+    0.0,          0.000303527,  0.000607054,  0.000910581,  0.001214108,  0.001517635,  0.001821162,  0.0021246888,
+    0.002428216,  0.0027317428, 0.00303527,   0.0033465358, 0.0036765074, 0.004024717,  0.004391442,  0.0047769533,
+    0.0051815165, 0.0056053917, 0.006048833,  0.0065120906, 0.00699541,   0.007499032,  0.008023193,  0.008568126,
+    0.009134059,  0.009721218,  0.010329823,  0.010960094,  0.011612245,  0.012286488,  0.0129830325, 0.013702083,
+    0.014443844,  0.015208514,  0.015996294,  0.016807375,  0.017641954,  0.01850022,   0.019382361,  0.020288562,
+    0.02121901,   0.022173885,  0.023153367,  0.024157632,  0.02518686,   0.026241222,  0.027320892,  0.02842604,
+    0.029556835,  0.030713445,  0.031896032,  0.033104766,  0.034339808,  0.035601314,  0.03688945,   0.038204372,
+    0.039546236,  0.0409152,    0.04231141,   0.04373503,   0.045186203,  0.046665087,  0.048171826,  0.049706567,
+    0.051269457,  0.052860647,  0.054480277,  0.05612849,   0.05780543,   0.059511237,  0.061246052,  0.063010015,
+    0.064803265,  0.06662594,   0.06847817,   0.070360094,  0.07227185,   0.07421357,   0.07618538,   0.07818742,
+    0.08021982,   0.08228271,   0.08437621,   0.08650046,   0.08865558,   0.09084171,   0.093058966,  0.09530747,
+    0.09758735,   0.099898726,  0.10224173,   0.104616486,  0.107023105,  0.10946171,   0.11193243,   0.114435375,
+    0.116970666,  0.11953843,   0.122138776,  0.12477182,   0.12743768,   0.13013647,   0.13286832,   0.13563333,
+    0.13843161,   0.14126329,   0.14412847,   0.14702727,   0.14995979,   0.15292615,   0.15592647,   0.15896083,
+    0.16202937,   0.1651322,    0.1682694,    0.17144111,   0.1746474,    0.17788842,   0.18116425,   0.18447499,
+    0.18782078,   0.19120169,   0.19461784,   0.19806932,   0.20155625,   0.20507874,   0.20863687,   0.21223076,
+    0.2158605,    0.2195262,    0.22322796,   0.22696587,   0.23074006,   0.23455058,   0.23839757,   0.24228112,
+    0.24620132,   0.25015828,   0.2541521,    0.25818285,   0.26225066,   0.2663556,    0.2704978,    0.2746773,
+    0.27889428,   0.28314874,   0.28744084,   0.29177064,   0.29613826,   0.30054379,   0.3049873,    0.30946892,
+    0.31398872,   0.31854677,   0.3231432,    0.3277781,    0.33245152,   0.33716363,   0.34191442,   0.34670407,
+    0.3515326,    0.35640013,   0.3613068,    0.3662526,    0.3712377,    0.37626213,   0.38132602,   0.38642943,
+    0.39157248,   0.39675522,   0.40197778,   0.4072402,    0.4125426,    0.41788507,   0.42326766,   0.4286905,
+    0.43415365,   0.43965718,   0.4452012,    0.4507858,    0.45641103,   0.462077,     0.4677838,    0.47353148,
+    0.47932017,   0.48514995,   0.49102086,   0.49693298,   0.5028865,    0.50888133,   0.5149177,    0.52099556,
+    0.5271151,    0.5332764,    0.5394795,    0.54572445,   0.55201143,   0.5583404,    0.5647115,    0.57112485,
+    0.57758045,   0.58407843,   0.59061885,   0.59720176,   0.60382736,   0.61049557,   0.6172066,    0.6239604,
+    0.63075715,   0.63759685,   0.6444797,    0.65140563,   0.65837485,   0.6653873,    0.67244315,   0.6795425,
+    0.6866853,    0.69387174,   0.7011019,    0.70837575,   0.7156935,    0.7230551,    0.73046076,   0.7379104,
+    0.7454042,    0.7529422,    0.7605245,    0.76815116,   0.7758222,    0.7835378,    0.7912979,    0.7991027,
+    0.80695224,   0.8148466,    0.82278574,   0.8307699,    0.838799,     0.8468732,    0.8549926,    0.8631572,
+    0.8713671,    0.8796224,    0.8879231,    0.8962694,    0.9046612,    0.91309863,   0.92158186,   0.9301109,
+    0.9386857,    0.9473065,    0.9559733,    0.9646863,    0.9734453,    0.9822506,    0.9911021,    1.0,
+];
@@ -0,0 +1,35 @@
+// Copyright 2017, Reizner Evgeniy <razrfalcon@gmail.com>.
+// See the COPYRIGHT file at the top-level directory of this distribution.
+// Licensed under the MIT license, see the LICENSE file or <http://opensource.org/licenses/MIT>
+
+//! An adapter for the `image` crate.
+
+use alloc::vec::Vec;
+
+use image::DynamicImage;
+
+use {get_palette, get_palette_u16, Color, ColorFormat, Error};
+
+/// Returns a representative color palette of an `image` crate image.
+///
+/// 8 and 16 bit images are processed without conversion.
+/// Other images are converted into RGBA8 first.
+///
+/// See `get_palette` for arguments description.
+pub fn get_palette_from_dynamic_image(
+    image: &DynamicImage,
+    quality: u8,
+    max_colors: u8,
+) -> Result<Vec<Color>, Error> {
+    match *image {
+        DynamicImage::ImageLuma8(ref buffer) => get_palette(buffer, ColorFormat::Gray, quality, max_colors),
+        DynamicImage::ImageLumaA8(ref buffer) => get_palette(buffer, ColorFormat::GrayAlpha, quality, max_colors),
+        DynamicImage::ImageRgb8(ref buffer) => get_palette(buffer, ColorFormat::Rgb, quality, max_colors),
+        DynamicImage::ImageRgba8(ref buffer) => get_palette(buffer, ColorFormat::Rgba, quality, max_colors),
+        DynamicImage::ImageLuma16(ref buffer) => get_palette_u16(buffer, ColorFormat::Gray, quality, max_colors),
+        DynamicImage::ImageLumaA16(ref buffer) => get_palette_u16(buffer, ColorFormat::GrayAlpha, quality, max_colors),
+        DynamicImage::ImageRgb16(ref buffer) => get_palette_u16(buffer, ColorFormat::Rgb, quality, max_colors),
+        DynamicImage::ImageRgba16(ref buffer) => get_palette_u16(buffer, ColorFormat::Rgba, quality, max_colors),
+        _ => get_palette(&image.to_rgba8(), ColorFormat::Rgba, quality, max_colors),
+    }
+}
@@ -10,44 +10,699 @@ The implementation itself is a heavily modified
 [Swift version](https://github.com/yamoridon/ColorThiefSwift) of the same algorithm.
 */
 
+#![no_std]
 #![forbid(unsafe_code)]
 #![warn(missing_docs)]
 
-extern crate rgb;
-
-use std::cmp;
-use std::fmt;
-use std::error;
-use std::u8;
+#[cfg(feature = "std")]
+extern crate std;
 
+#[macro_use]
+extern crate alloc;
+extern crate rgb;
+#[cfg(feature = "serde")]
+#[macro_use]
+extern crate serde;
+#[cfg(feature = "rayon")]
+extern crate rayon;
+#[cfg(feature = "image")]
+extern crate image;
+#[cfg(feature = "simd")]
+extern crate wide;
+
+use core::cmp;
+use core::fmt;
+use core::ops;
+
+use alloc::collections::BinaryHeap;
+use alloc::vec::Vec;
+
+use thumbnail::Thumbnail;
+
+/// An RGB color.
+///
+/// When the `serde` feature is enabled, `Color` and therefore `Vec<Color>`
+/// implement `serde::Serialize` and `serde::Deserialize` as well.
 pub use rgb::RGB8 as Color;
 
-const SIGNAL_BITS: i32              = 5; // Use only upper 5 bits of 8 bits.
-const RIGHT_SHIFT: i32              = 8 - SIGNAL_BITS;
-const MULTIPLIER: i32               = 1 << RIGHT_SHIFT;
-const MULTIPLIER_64: f64            = MULTIPLIER as f64;
-const HISTOGRAM_SIZE: usize         = 1 << (3 * SIGNAL_BITS);
-const VBOX_LENGTH: usize            = 1 << SIGNAL_BITS;
-const FRACTION_BY_POPULATION: f64   = 0.75;
-const MAX_ITERATIONS: i32           = 1000;
+/// An RGBA color.
+pub use rgb::RGBA8;
+
+pub mod color;
+#[cfg(feature = "css-names")]
+mod css_names;
+#[cfg(feature = "image")]
+mod dynamic_image;
+mod octree;
+mod palette;
+#[cfg(feature = "simd")]
+mod simd;
+mod swatches;
+mod thumbnail;
+
+#[cfg(feature = "css-names")]
+pub use css_names::nearest_css_name;
+#[cfg(feature = "image")]
+pub use dynamic_image::get_palette_from_dynamic_image;
+pub use palette::{palette, Palette};
+pub use swatches::{swatches, Swatches};
+
+const SIGNAL_BITS: u8               = 5; // Use only upper 5 bits of 8 bits.
+const FRACTION_BY_POPULATION: f32   = 0.75;
+const MAX_ITERATIONS: u32           = 1000;
+const WEIGHT_SCALE: u64             = 255; // Fixed-point scale of weighted pixels.
+const MAX_SATURATION_WEIGHT: f32    = 1000.0;
+const GRAYSCALE_OUTLIERS: f32       = 0.01; // A fraction of colored pixels in a grayscale image.
+const AUTO_SAMPLES: usize           = 10_000; // A number of samples for the zero quality.
 
 /// Represent a color format of an underlying image data.
 #[allow(missing_docs)]
 #[derive(Clone,Copy,PartialEq,Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum ColorFormat {
     Rgb,
     Rgba,
     Argb,
+    Abgr,
     Bgr,
     Bgra,
+    /// RGB with an ignored padding byte.
+    Rgbx,
+    /// BGR with an ignored padding byte.
+    Bgrx,
+    Gray,
+    GrayAlpha,
+    RgbaPremultiplied,
+    /// Converted into RGB using the naive `r = 255 * (1 - c) * (1 - k)` formula.
+    ///
+    /// No color profiles are applied.
+    Cmyk,
+    /// A full range BT.601 YCbCr, as used by JPEG.
+    YCbCr,
+}
+
+/// An order of colors in the output palette.
+#[derive(Clone,Copy,PartialEq,Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum SortOrder {
+    /// The most populated color goes first,
+    /// followed by other colors ordered by population times volume,
+    /// just like in the original implementation.
+    ///
+    /// `CompatMode::ColorThiefPy` keeps the reference order for all colors,
+    /// so the first color is not necessarily the most populated one.
+    Population,
+    /// From dark to light colors using `color::relative_luminance`.
+    LuminanceAsc,
+    /// From light to dark colors using `color::relative_luminance`.
+    LuminanceDesc,
+    /// By hue, starting from red. Gray colors go first.
+    Hue,
+}
+
+/// A reference implementation to match.
+#[derive(Clone,Copy,PartialEq,Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum CompatMode {
+    /// The behavior of the previous versions of this crate.
+    ///
+    /// Samples every `quality * channels` pixel and resolves
+    /// boxes with the same population by volume and bounds.
+    /// Puts the most populated color first, see `SortOrder::Population`.
+    Default,
+    /// Follows the Python [colorthief](https://github.com/fengsp/color-thief-py) 0.2.1 algorithm.
+    ///
+    /// Samples every `quality` pixel, resolves ties like the reference implementation
+    /// and moves boxes to the second queue in its order.
+    /// Box averages and cut positions are truncated, just like in colorthief-py,
+    /// so `RoundingMode::Truncate` must be used to match it.
+    ///
+    /// colorthief-py decodes images with Pillow, so colors of the same file can differ slightly.
+    ColorThiefPy,
+}
+
+/// A rounding of box averages and cut positions.
+#[derive(Clone,Copy,PartialEq,Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum RoundingMode {
+    /// Truncates toward zero.
+    ///
+    /// Both the original color-thief JS and colorthief-py truncate,
+    /// so this mode matches either of them, depending on `CompatMode`.
+    Truncate,
+    /// Rounds to the nearest value.
+    ///
+    /// Slightly more accurate, but matches neither reference implementation.
+    Nearest,
+}
+
+/// A quantization algorithm.
+#[derive(Clone,Copy,PartialEq,Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Algorithm {
+    /// The modified median cut from the original color-thief.
+    MedianCut,
+    /// Octree color quantization.
+    ///
+    /// Can give better results on images with many subtle gradients.
+    /// `CompatMode` and `Options::refill` have no effect.
+    Octree,
+}
+
+/// A way to handle semi-transparent pixels.
+#[derive(Clone,Copy,PartialEq,Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum AlphaMode {
+    /// Pixels with an alpha lower than `Options::alpha_threshold` are skipped,
+    /// other pixels are fully counted.
+    Threshold,
+    /// Pixels are counted proportionally to their opacity.
+    ///
+    /// Only fully transparent pixels are skipped.
+    /// The opacity is counted in `1 / 255` steps, so each alpha value has its own weight.
+    Weighted,
+}
+
+/// A color space used for quantization.
+#[derive(Clone,Copy,PartialEq,Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ColorSpace {
+    /// Boxes are cut along sRGB axes.
+    Rgb,
+    /// Boxes are cut along CIE Lab axes, which better match human perception.
+    ///
+    /// The histogram stores Lab colors, see `Histogram::counts`.
+    /// Palette colors are converted back into sRGB.
+    /// `Options::linear_average` has no effect.
+    Lab,
+}
+
+/// A weighting of pixels by their position.
+#[derive(Clone,Copy,PartialEq,Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum SpatialWeight {
+    /// All pixels have the same weight.
+    Uniform,
+    /// Pixels closer to the image center have a higher weight.
+    ///
+    /// Pixels in the center count 4 times more than ones in the corners.
+    Center,
+}
+
+/// A way to pick sampled pixels.
+#[derive(Clone,Copy,PartialEq,Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Sampling {
+    /// Every `step` pixel.
+    Stride,
+    /// Pseudo-random pixels.
+    ///
+    /// Avoids aliasing with regular patterns, like stripes.
+    /// The same seed produces the same palette.
+    Random {
+        /// A seed of the pseudo-random generator.
+        seed: u64,
+    },
+    /// Every pixel of a downscaled image.
+    ///
+    /// The image is area-averaged to at most `max_dim` pixels on each side,
+    /// which gives more stable palettes than skipping pixels.
+    /// The quality has no effect.
+    ///
+    /// Requires image dimensions, see `Quantizer::build_palette_with_size`.
+    /// `Error::MissingDimensions` otherwise.
+    /// `HistogramBuilder` samples by stride instead.
+    Downscale {
+        /// A maximum width and height of the downscaled image.
+        ///
+        /// Must be positive. `Error::InvalidMaxDim` otherwise.
+        max_dim: u32,
+    },
+}
+
+/// Additional quantization options.
+#[derive(Clone,Copy,PartialEq,Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Options {
+    /// Pixels with an alpha value lower than this one will be skipped.
+    ///
+    /// 0 means that all pixels will be used regardless of transparency.
+    /// Used only by `AlphaMode::Threshold`.
+    ///
+    /// Default: 125
+    pub alpha_threshold: u8,
+
+    /// A way to handle semi-transparent pixels.
+    ///
+    /// Default: `AlphaMode::Threshold`
+    pub alpha_mode: AlphaMode,
+
+    /// A background to blend semi-transparent pixels over.
+    ///
+    /// Blended pixels are opaque, so `alpha_threshold` and `alpha_mode` have no effect.
+    /// Note that pixels blended over white can be skipped by `ignore_white`.
+    ///
+    /// Default: `None`
+    pub composite_over: Option<Color>,
+
+    /// Average colors of skipped transparent pixels separately.
+    ///
+    /// Useful for blending edges with translucent pixels.
+    /// See `Palette::transparent_average`.
+    ///
+    /// Default: false
+    pub transparent_average: bool,
+
+    /// Pixels with all channels greater than this value will be skipped.
+    ///
+    /// `None` means that white pixels will not be skipped.
+    ///
+    /// Default: `Some(250)`
+    pub ignore_white: Option<u8>,
+
+    /// Return white as the only palette color when all visible pixels
+    /// were skipped by `ignore_white`.
+    ///
+    /// `Error::OnlyWhite` will be returned otherwise.
+    ///
+    /// Default: true
+    pub white_fallback: bool,
+
+    /// Pixels with all channels lower than this value will be skipped.
+    ///
+    /// Helps with black borders and vignettes.
+    /// `None` means that black pixels will not be skipped.
+    ///
+    /// Default: `None`
+    pub ignore_black: Option<u8>,
+
+    /// A number of upper bits of each channel used by the histogram.
+    ///
+    /// Higher values allow to distinguish similar colors
+    /// at the cost of memory and performance.
+    /// The histogram has `1 << (3 * signal_bits)` cells.
+    ///
+    /// Range: 1..8. `Error::InvalidSignalBits` otherwise.
+    ///
+    /// Default: 5
+    pub signal_bits: u8,
+
+    /// An order of colors in the output palette.
+    ///
+    /// Default: `SortOrder::Population`
+    pub sort_order: SortOrder,
+
+    /// A reference implementation to match.
+    ///
+    /// Default: `CompatMode::Default`
+    pub compat_mode: CompatMode,
+
+    /// A way to pick sampled pixels.
+    ///
+    /// The number of `Stride` and `Random` samples is defined by the quality.
+    ///
+    /// Default: `Sampling::Stride`
+    pub sampling: Sampling,
+
+    /// Average colors in a linear light instead of sRGB.
+    ///
+    /// Produces less muddy colors for boxes with high contrast.
+    ///
+    /// Default: false
+    pub linear_average: bool,
+
+    /// A rounding of sRGB box averages and median cut positions.
+    ///
+    /// Default: `RoundingMode::Truncate`
+    pub rounding: RoundingMode,
+
+    /// Makes saturated pixels count more than gray ones.
+    ///
+    /// Each pixel is counted as `1 + saturation_weight * saturation`,
+    /// where saturation is in a 0..1 range.
+    /// Helps to surface vivid colors on images with large gray regions.
+    ///
+    /// Large weights would overflow pixel counts, so they are limited.
+    ///
+    /// Range: 0..1000. `Error::InvalidSaturationWeight` otherwise.
+    ///
+    /// Default: 0
+    pub saturation_weight: f32,
+
+    /// Palette colors closer than this distance will be merged.
+    ///
+    /// Merged colors are averaged, weighted by the number of pixels.
+    /// 0 disables merging.
+    ///
+    /// Default: 0
+    pub min_distance: u8,
+
+    /// Cut more boxes to replace colors removed by `min_distance` merging.
+    ///
+    /// Default: false
+    pub refill: bool,
+
+    /// A number of k-means iterations to refine palette colors.
+    ///
+    /// Moves each color to the centroid of the histogram cells closest to it.
+    /// Iterations stop earlier when colors do not change anymore.
+    ///
+    /// Default: 0
+    pub refine_iterations: u32,
+
+    /// Replace palette colors with the closest exact pixel colors.
+    ///
+    /// Quantization shifts colors slightly, so pure colors of flat graphics,
+    /// like (255, 0, 0), are not reproduced verbatim.
+    /// When set, each palette color is snapped to the nearest pixel color of its box,
+    /// but only to colors without other colors in the same histogram cell
+    /// and not farther than a histogram cell size.
+    /// Photos rarely have such colors, so they are mostly left intact.
+    ///
+    /// Requires more memory, since a color of each histogram cell is stored.
+    ///
+    /// Default: false
+    pub snap_exact: bool,
+
+    /// A quantization algorithm.
+    ///
+    /// Default: `Algorithm::MedianCut`
+    pub algorithm: Algorithm,
+
+    /// A maximum number of cut attempts during each median cut phase.
+    ///
+    /// Large `max_colors` values may require more iterations.
+    ///
+    /// Default: 1000
+    pub max_iterations: u32,
+
+    /// A fraction of colors generated by population before switching
+    /// to the population times volume ordering.
+    ///
+    /// Lower values shift weighting from population toward volume,
+    /// which helps to keep rare, but distinct colors.
+    ///
+    /// Range: 0..1. `Error::InvalidPopulationFraction` otherwise.
+    ///
+    /// Default: 0.75
+    pub population_fraction: f32,
+
+    /// Return `Error::InsufficientColors` instead of a palette
+    /// when an image has less than `max_colors` distinct colors after reducing to `signal_bits`.
+    ///
+    /// Note that the median cut usually produces `max_colors - 1` colors,
+    /// just like the original implementation, which is not an error.
+    ///
+    /// Default: false
+    pub exact_colors: bool,
+
+    /// A weighting of pixels by their position.
+    ///
+    /// `SpatialWeight::Center` requires image dimensions, see `Quantizer::build_palette_with_size`.
+    /// `Error::MissingDimensions` otherwise.
+    ///
+    /// Default: `SpatialWeight::Uniform`
+    pub spatial_weight: SpatialWeight,
+
+    /// A minimal fraction of sampled pixels a palette color must represent.
+    ///
+    /// Drops tiny clusters, like antialiasing pixels.
+    /// The most populated color is always kept.
+    ///
+    /// Range: 0..1. `Error::InvalidMinPopulation` otherwise.
+    ///
+    /// Default: 0
+    pub min_population: f32,
+
+    /// A color space used for quantization.
+    ///
+    /// Default: `ColorSpace::Rgb`
+    pub color_space: ColorSpace,
+
+    /// A maximum number of pixels in an image.
+    ///
+    /// Larger images are rejected with `Error::ImageTooLarge` before processing.
+    /// Checked for each image added to a `Histogram`
+    /// and for all the chunks pushed to a `HistogramBuilder`.
+    ///
+    /// Default: `None`
+    pub max_pixels: Option<usize>,
+}
+
+impl ColorFormat {
+    /// Returns a number of bytes per pixel.
+    ///
+    /// Buffer lengths must be a multiple of it. `Error::InvalidLength` otherwise.
+    pub fn bytes_per_pixel(&self) -> usize {
+        self.channels()
+    }
+
+    /// Returns a number of channels per pixel, including ignored padding bytes.
+    pub fn channels(&self) -> usize {
+        match *self {
+            ColorFormat::Rgb => 3,
+            ColorFormat::Rgba => 4,
+            ColorFormat::Argb => 4,
+            ColorFormat::Abgr => 4,
+            ColorFormat::Bgr => 3,
+            ColorFormat::Bgra => 4,
+            ColorFormat::Rgbx => 4,
+            ColorFormat::Bgrx => 4,
+            ColorFormat::Gray => 1,
+            ColorFormat::GrayAlpha => 2,
+            ColorFormat::RgbaPremultiplied => 4,
+            ColorFormat::Cmyk => 4,
+            ColorFormat::YCbCr => 3,
+        }
+    }
+}
+
+impl core::str::FromStr for ColorFormat {
+    type Err = Error;
+
+    /// Parses a lowercase variant name, like `rgba` or `gray_alpha`.
+    ///
+    /// The case is ignored.
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let formats = [
+            ("rgb", ColorFormat::Rgb),
+            ("rgba", ColorFormat::Rgba),
+            ("argb", ColorFormat::Argb),
+            ("abgr", ColorFormat::Abgr),
+            ("bgr", ColorFormat::Bgr),
+            ("bgra", ColorFormat::Bgra),
+            ("rgbx", ColorFormat::Rgbx),
+            ("bgrx", ColorFormat::Bgrx),
+            ("gray", ColorFormat::Gray),
+            ("gray_alpha", ColorFormat::GrayAlpha),
+            ("rgba_premultiplied", ColorFormat::RgbaPremultiplied),
+            ("cmyk", ColorFormat::Cmyk),
+            ("ycbcr", ColorFormat::YCbCr),
+        ];
+
+        formats.iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(text))
+            .map(|&(_, format)| format)
+            .ok_or(Error::UnknownColorFormat)
+    }
+}
+
+impl core::convert::TryFrom<u8> for ColorFormat {
+    type Error = Error;
+
+    /// Converts a numeric tag, which follows the declaration order:
+    ///
+    /// | Tag | Format              | Tag | Format              |
+    /// |-----|---------------------|-----|---------------------|
+    /// | 0   | `Rgb`               | 7   | `Bgrx`              |
+    /// | 1   | `Rgba`              | 8   | `Gray`              |
+    /// | 2   | `Argb`              | 9   | `GrayAlpha`         |
+    /// | 3   | `Abgr`              | 10  | `RgbaPremultiplied` |
+    /// | 4   | `Bgr`               | 11  | `Cmyk`              |
+    /// | 5   | `Bgra`              | 12  | `YCbCr`             |
+    /// | 6   | `Rgbx`              |     |                     |
+    ///
+    /// Other tags are `Error::UnknownColorFormat`.
+    fn try_from(tag: u8) -> Result<Self, Self::Error> {
+        match tag {
+            0 => Ok(ColorFormat::Rgb),
+            1 => Ok(ColorFormat::Rgba),
+            2 => Ok(ColorFormat::Argb),
+            3 => Ok(ColorFormat::Abgr),
+            4 => Ok(ColorFormat::Bgr),
+            5 => Ok(ColorFormat::Bgra),
+            6 => Ok(ColorFormat::Rgbx),
+            7 => Ok(ColorFormat::Bgrx),
+            8 => Ok(ColorFormat::Gray),
+            9 => Ok(ColorFormat::GrayAlpha),
+            10 => Ok(ColorFormat::RgbaPremultiplied),
+            11 => Ok(ColorFormat::Cmyk),
+            12 => Ok(ColorFormat::YCbCr),
+            _ => Err(Error::UnknownColorFormat),
+        }
+    }
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            alpha_threshold: 125,
+            alpha_mode: AlphaMode::Threshold,
+            composite_over: None,
+            transparent_average: false,
+            ignore_white: Some(250),
+            white_fallback: true,
+            ignore_black: None,
+            signal_bits: SIGNAL_BITS,
+            sort_order: SortOrder::Population,
+            compat_mode: CompatMode::Default,
+            sampling: Sampling::Stride,
+            linear_average: false,
+            rounding: RoundingMode::Truncate,
+            saturation_weight: 0.0,
+            min_distance: 0,
+            refill: false,
+            refine_iterations: 0,
+            snap_exact: false,
+            algorithm: Algorithm::MedianCut,
+            max_iterations: MAX_ITERATIONS,
+            population_fraction: FRACTION_BY_POPULATION,
+            exact_colors: false,
+            spatial_weight: SpatialWeight::Uniform,
+            min_population: 0.0,
+            color_space: ColorSpace::Rgb,
+            max_pixels: None,
+        }
+    }
+}
+
+impl Options {
+    /// Returns an index of the `n`th sampled pixel or `None` when it's masked out.
+    fn sample_index<T: Channel>(&self, image: &Image<T>, step: usize, n: usize) -> Option<usize> {
+        let index = match self.sampling {
+            Sampling::Stride | Sampling::Downscale { .. } => n * step,
+            Sampling::Random { seed } => {
+                (splitmix64(seed, n as u64) % image.pixel_count as u64) as usize
+            }
+        };
+
+        if let Some(mask) = image.mask {
+            if mask[index] == 0 {
+                return None;
+            }
+        }
+
+        Some(index)
+    }
+
+    /// Checks that an image can be processed with these options.
+    fn check_image<T: Channel>(&self, image: &Image<T>) -> Result<(), Error> {
+        let downscale = matches!(self.sampling, Sampling::Downscale { .. });
+        let center = self.spatial_weight == SpatialWeight::Center;
+        if (downscale || center) && !image.sized {
+            return Err(Error::MissingDimensions);
+        }
+
+        self.check_size(image.pixel_count)
+    }
+
+    /// Checks that an image is not larger than `max_pixels`.
+    fn check_size(&self, pixel_count: usize) -> Result<(), Error> {
+        match self.max_pixels {
+            Some(max) if pixel_count > max => Err(Error::ImageTooLarge),
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks that a pixel should be skipped because of its alpha.
+    fn is_transparent(&self, a: u8) -> bool {
+        match self.alpha_mode {
+            AlphaMode::Threshold => a < self.alpha_threshold,
+            AlphaMode::Weighted => a == 0,
+        }
+    }
+
+    /// Checks that a pixel should be skipped because it's white.
+    fn is_white(&self, r: u8, g: u8, b: u8) -> bool {
+        match self.ignore_white {
+            Some(t) => r > t && g > t && b > t,
+            None => false,
+        }
+    }
+
+    /// Checks that a pixel should be skipped.
+    fn skips(&self, r: u8, g: u8, b: u8, a: u8) -> bool {
+        // If pixel is mostly transparent or white.
+        if self.is_transparent(a) {
+            return true;
+        }
+
+        if self.is_white(r, g, b) {
+            return true;
+        }
+
+        if let Some(t) = self.ignore_black {
+            if r < t && g < t && b < t {
+                return true;
+            }
+        }
+
+        false
+    }
+}
+
+/// A rectangular region of an image in pixels.
+#[allow(missing_docs)]
+#[derive(Clone,Copy,PartialEq,Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Rect {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Rect {
+    /// Creates a new `Rect`.
+    pub fn new(x: usize, y: usize, width: usize, height: usize) -> Self {
+        Rect { x, y, width, height }
+    }
 }
 
 /// List of all errors.
 #[allow(missing_docs)]
 #[derive(Clone,Copy,PartialEq,Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Error {
     InvalidVBox,
     VBoxCutFailed,
+    EmptyPalette,
+    InvalidQuality,
+    InvalidMaxColors,
+    EmptyImage,
+    InvalidStride,
+    EmptyRegion,
+    InvalidSignalBits,
+    InvalidFraction,
+    InvalidSaturationWeight,
+    InvalidPopulationFraction,
+    InsufficientColors { found: u8, requested: u8 },
+    InvalidMask,
+    InvalidHistogram,
+    InvalidMinPopulation,
+    PlaneLengthMismatch,
+    Cancelled,
+    InvalidLength,
+    NoVisiblePixels,
+    OnlyWhite,
+    InvalidMaxDim,
+    ImageTooLarge,
+    InvalidBitDepth,
+    InvalidIndex,
+    UnknownColorFormat,
+    ColorFormatMismatch,
+    MissingDimensions,
+    InvalidPalette,
 }
 
 impl fmt::Display for Error {
@@ -55,13 +710,435 @@ impl fmt::Display for Error {
         let msg = match *self {
             Error::InvalidVBox => "an invalid VBox",
             Error::VBoxCutFailed => "failed to cut a VBox",
+            Error::EmptyPalette => "an empty palette",
+            Error::InvalidQuality => "quality must be in a 0..10 range",
+            Error::InvalidMaxColors => "max colors must be in a 2..255 range",
+            Error::EmptyImage => "an image has no pixels to process",
+            Error::InvalidStride => "a row stride is smaller than a row",
+            Error::EmptyRegion => "a region has no pixels",
+            Error::InvalidSignalBits => "signal bits must be in a 1..8 range",
+            Error::InvalidFraction => "fraction must be in a 0..1 range",
+            Error::InvalidSaturationWeight => "saturation weight must be in a 0..1000 range",
+            Error::InvalidPopulationFraction => "population fraction must be in a 0..1 range",
+            Error::InsufficientColors { found, requested } => {
+                return write!(f, "found {} colors out of {} requested", found, requested);
+            }
+            Error::InvalidMask => "a mask length doesn't match the number of pixels",
+            Error::InvalidHistogram => "a number of histogram cells doesn't match signal bits",
+            Error::InvalidMinPopulation => "min population must be in a 0..1 range",
+            Error::PlaneLengthMismatch => "color planes have different lengths",
+            Error::Cancelled => "cancelled by a progress callback",
+            Error::InvalidLength => "a buffer length is not a multiple of the pixel size",
+            Error::NoVisiblePixels => "all pixels are transparent",
+            Error::OnlyWhite => "all visible pixels are white",
+            Error::InvalidMaxDim => "downscale max dimension must be positive",
+            Error::ImageTooLarge => "an image has more pixels than allowed",
+            Error::InvalidBitDepth => "bits per index must be 1, 2, 4 or 8",
+            Error::InvalidIndex => "a color index is out of the palette range",
+            Error::UnknownColorFormat => "an unknown color format name or tag",
+            Error::ColorFormatMismatch => "chunks have different color formats",
+            Error::MissingDimensions => "options require image dimensions",
+            Error::InvalidPalette => "a palette must have one count per color and at least one color",
         };
 
         write!(f, "{}", msg)
     }
 }
 
-impl error::Error for Error {}
+#[cfg(feature = "std")]
+impl std::error::Error for Error {}
+
+/// A palette quantizer builder.
+///
+/// # Example
+///
+/// ```no_run
+/// # let pixels = [0; 3];
+/// use color_thief::{ColorFormat, Quantizer};
+///
+/// let colors = Quantizer::new()
+///     .quality(5)
+///     .max_colors(8)
+///     .ignore_white(false)
+///     .build_palette(&pixels, ColorFormat::Rgb)
+///     .unwrap();
+/// ```
+#[derive(Clone,Debug)]
+pub struct Quantizer {
+    quality: u8,
+    max_colors: u8,
+    options: Options,
+    /// A histogram reused by `build_palette_reusing`.
+    histogram: Option<Histogram>,
+}
+
+impl Default for Quantizer {
+    fn default() -> Self {
+        Quantizer {
+            quality: 10,
+            max_colors: 10,
+            options: Options::default(),
+            histogram: None,
+        }
+    }
+}
+
+impl Quantizer {
+    /// Creates a new quantizer with default options.
+    ///
+    /// Quality is 10 and max colors is 10.
+    pub fn new() -> Self {
+        Quantizer::default()
+    }
+
+    /// Sets quality of an output colors.
+    ///
+    /// See `get_palette` for details.
+    pub fn quality(mut self, quality: u8) -> Self {
+        self.quality = quality;
+        self
+    }
+
+    /// Sets a number of colors in the output palette.
+    ///
+    /// See `get_palette` for details.
+    pub fn max_colors(mut self, max_colors: u8) -> Self {
+        self.max_colors = max_colors;
+        self
+    }
+
+    /// Sets all the additional options at once.
+    pub fn options(mut self, options: Options) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Sets `Options::alpha_threshold`.
+    pub fn alpha_threshold(mut self, threshold: u8) -> Self {
+        self.options.alpha_threshold = threshold;
+        self
+    }
+
+    /// Enables or disables near-white pixels skipping.
+    ///
+    /// Uses the default threshold when enabled.
+    pub fn ignore_white(mut self, ignore: bool) -> Self {
+        self.options.ignore_white = if ignore { Options::default().ignore_white } else { None };
+        self
+    }
+
+    /// Sets `Options::ignore_white` to the specified threshold.
+    pub fn white_threshold(mut self, threshold: u8) -> Self {
+        self.options.ignore_white = Some(threshold);
+        self
+    }
+
+    /// Sets `Options::alpha_mode`.
+    pub fn alpha_mode(mut self, mode: AlphaMode) -> Self {
+        self.options.alpha_mode = mode;
+        self
+    }
+
+    /// Sets `Options::composite_over`.
+    pub fn composite_over(mut self, background: Option<Color>) -> Self {
+        self.options.composite_over = background;
+        self
+    }
+
+    /// Sets `Options::transparent_average`.
+    pub fn transparent_average(mut self, average: bool) -> Self {
+        self.options.transparent_average = average;
+        self
+    }
+
+    /// Sets `Options::white_fallback`.
+    pub fn white_fallback(mut self, fallback: bool) -> Self {
+        self.options.white_fallback = fallback;
+        self
+    }
+
+    /// Sets `Options::ignore_black` to the specified threshold.
+    pub fn black_threshold(mut self, threshold: u8) -> Self {
+        self.options.ignore_black = Some(threshold);
+        self
+    }
+
+    /// Sets `Options::signal_bits`.
+    pub fn signal_bits(mut self, bits: u8) -> Self {
+        self.options.signal_bits = bits;
+        self
+    }
+
+    /// Sets `Options::sort_order`.
+    pub fn sort_order(mut self, order: SortOrder) -> Self {
+        self.options.sort_order = order;
+        self
+    }
+
+    /// Sets `Options::compat_mode`.
+    pub fn compat_mode(mut self, mode: CompatMode) -> Self {
+        self.options.compat_mode = mode;
+        self
+    }
+
+    /// Sets `Options::sampling`.
+    pub fn sampling(mut self, sampling: Sampling) -> Self {
+        self.options.sampling = sampling;
+        self
+    }
+
+    /// Sets `Options::linear_average`.
+    pub fn linear_average(mut self, linear: bool) -> Self {
+        self.options.linear_average = linear;
+        self
+    }
+
+    /// Sets `Options::rounding`.
+    pub fn rounding(mut self, rounding: RoundingMode) -> Self {
+        self.options.rounding = rounding;
+        self
+    }
+
+    /// Sets `Options::saturation_weight`.
+    pub fn saturation_weight(mut self, weight: f32) -> Self {
+        self.options.saturation_weight = weight;
+        self
+    }
+
+    /// Sets `Options::min_distance`.
+    pub fn min_distance(mut self, distance: u8) -> Self {
+        self.options.min_distance = distance;
+        self
+    }
+
+    /// Sets `Options::refill`.
+    pub fn refill(mut self, refill: bool) -> Self {
+        self.options.refill = refill;
+        self
+    }
+
+    /// Sets `Options::refine_iterations`.
+    pub fn refine_iterations(mut self, iterations: u32) -> Self {
+        self.options.refine_iterations = iterations;
+        self
+    }
+
+    /// Sets `Options::snap_exact`.
+    pub fn snap_exact(mut self, snap: bool) -> Self {
+        self.options.snap_exact = snap;
+        self
+    }
+
+    /// Sets `Options::algorithm`.
+    pub fn algorithm(mut self, algorithm: Algorithm) -> Self {
+        self.options.algorithm = algorithm;
+        self
+    }
+
+    /// Sets `Options::max_iterations`.
+    pub fn max_iterations(mut self, iterations: u32) -> Self {
+        self.options.max_iterations = iterations;
+        self
+    }
+
+    /// Sets `Options::population_fraction`.
+    pub fn population_fraction(mut self, fraction: f32) -> Self {
+        self.options.population_fraction = fraction;
+        self
+    }
+
+    /// Sets `Options::exact_colors`.
+    pub fn exact_colors(mut self, exact: bool) -> Self {
+        self.options.exact_colors = exact;
+        self
+    }
+
+    /// Sets `Options::spatial_weight`.
+    pub fn spatial_weight(mut self, weight: SpatialWeight) -> Self {
+        self.options.spatial_weight = weight;
+        self
+    }
+
+    /// Sets `Options::min_population`.
+    pub fn min_population(mut self, fraction: f32) -> Self {
+        self.options.min_population = fraction;
+        self
+    }
+
+    /// Sets `Options::color_space`.
+    pub fn color_space(mut self, space: ColorSpace) -> Self {
+        self.options.color_space = space;
+        self
+    }
+
+    /// Sets `Options::max_pixels`.
+    pub fn max_pixels(mut self, max_pixels: Option<usize>) -> Self {
+        self.options.max_pixels = max_pixels;
+        self
+    }
+
+    /// Returns a representative color palette of an image.
+    ///
+    /// See `get_palette` for arguments description.
+    pub fn build_palette(&self, pixels: &[u8], color_format: ColorFormat) -> Result<Vec<Color>, Error> {
+        let vboxes = get_vboxes(&Image::checked(pixels, color_format)?, self.quality, self.max_colors, &self.options)?;
+        Ok(vboxes.iter().map(|v| v.average).collect())
+    }
+
+    /// Returns a representative color palette of an image,
+    /// reusing the histogram allocated by a previous call.
+    ///
+    /// Avoids allocations when processing many images in a row.
+    /// The result is the same as `build_palette`.
+    ///
+    /// See `get_palette` for arguments description.
+    pub fn build_palette_reusing(
+        &mut self,
+        pixels: &[u8],
+        color_format: ColorFormat,
+    ) -> Result<Vec<Color>, Error> {
+        check_palette_args(self.quality, self.max_colors)?;
+
+        let image = Image::checked(pixels, color_format)?;
+        if image.pixels.is_empty() {
+            return Err(Error::EmptyImage);
+        }
+
+        // Options can be changed after the histogram was allocated.
+        let mut histogram = match self.histogram.take() {
+            Some(mut histogram) if histogram.options == self.options => {
+                histogram.clear();
+                histogram
+            }
+            _ => Histogram::with_options(self.options)?,
+        };
+
+        let step = quality_step(&image, self.quality, self.options.compat_mode);
+        let colors = histogram.fill(&image, step).and_then(|_| histogram.quantize(self.max_colors));
+        self.histogram = Some(histogram);
+        colors
+    }
+
+    /// Returns a representative color palette of an image, reporting a progress.
+    ///
+    /// `progress` is called periodically during the histogram building
+    /// with a 0..1 fraction of processed pixels.
+    /// Returning `false` aborts processing with `Error::Cancelled`.
+    ///
+    /// Pixels are processed sequentially, even with the `rayon` feature.
+    ///
+    /// See `get_palette` for other arguments description.
+    pub fn build_palette_with_progress(
+        &self,
+        pixels: &[u8],
+        color_format: ColorFormat,
+        progress: &mut dyn FnMut(f32) -> bool,
+    ) -> Result<Vec<Color>, Error> {
+        check_palette_args(self.quality, self.max_colors)?;
+
+        let image = Image::checked(pixels, color_format)?;
+        if image.pixels.is_empty() {
+            return Err(Error::EmptyImage);
+        }
+
+        let mut histogram = Histogram::with_options(self.options)?;
+        let step = quality_step(&image, self.quality, self.options.compat_mode);
+        histogram.fill_with_progress(&image, step, progress)?;
+        histogram.quantize(self.max_colors)
+    }
+
+    /// Removes pixels collected by `build_palette_reusing`.
+    ///
+    /// Keeps the allocated histogram memory.
+    pub fn reset(&mut self) {
+        if let Some(ref mut histogram) = self.histogram {
+            histogram.clear();
+        }
+    }
+
+    /// Returns a representative color palette of an image
+    /// with a mean squared error of the palette.
+    ///
+    /// The error is averaged over channels of sampled pixels replaced by
+    /// the closest palette color. Skipped pixels are not counted.
+    /// Useful to choose `max_colors` automatically.
+    ///
+    /// See `get_palette` for arguments description.
+    pub fn build_palette_with_error(
+        &self,
+        pixels: &[u8],
+        color_format: ColorFormat,
+    ) -> Result<(Vec<Color>, f32), Error> {
+        let image = Image::checked(pixels, color_format)?;
+        let vboxes = get_vboxes(&image, self.quality, self.max_colors, &self.options)?;
+        let colors: Vec<Color> = vboxes.iter().map(|v| v.average).collect();
+
+        let step = quality_step(&image, self.quality, self.options.compat_mode);
+        let mut error = 0u64;
+        let mut count = 0u64;
+        for (r, g, b, _) in sampled_pixels(&image, step, &self.options) {
+            let c = Color::new(r, g, b);
+            error += colors.iter().map(|p| distance_sq(*p, c)).min().unwrap_or(0) as u64;
+            count += 1;
+        }
+
+        let mse = if count == 0 { 0.0 } else { (error as f64 / (count * 3) as f64) as f32 };
+        Ok((colors, mse))
+    }
+
+    /// Returns a representative color palette of an image as a `Palette`.
+    ///
+    /// See `get_palette` for arguments description.
+    ///
+    /// Also returns an average of transparent pixels when `Options::transparent_average` is set.
+    pub fn build(&self, pixels: &[u8], color_format: ColorFormat) -> Result<Palette, Error> {
+        check_quality(self.quality)?;
+
+        let image = Image::checked(pixels, color_format)?;
+        let step = quality_step(&image, self.quality, self.options.compat_mode);
+        let histogram = get_histogram(&image, step, self.max_colors, &self.options, false)?;
+        let mut palette = Palette::from_vboxes(&quantize(&histogram, self.max_colors)?);
+        palette.transparent_average = histogram.transparent_average();
+        Ok(palette)
+    }
+
+    /// Returns a representative color palette of an image with an average alpha of each color.
+    ///
+    /// The alpha is averaged over non-skipped pixels only.
+    /// Formats without alpha always have 255.
+    ///
+    /// See `get_palette` for arguments description.
+    pub fn build_palette_with_alpha(
+        &self,
+        pixels: &[u8],
+        color_format: ColorFormat,
+    ) -> Result<Vec<RGBA8>, Error> {
+        check_quality(self.quality)?;
+
+        let image = Image::checked(pixels, color_format)?;
+        let step = quality_step(&image, self.quality, self.options.compat_mode);
+        let vboxes = get_vboxes_with_step(&image, step, self.max_colors, &self.options, true)?;
+        Ok(vboxes.iter().map(|v| v.average.with_alpha(v.alpha)).collect())
+    }
+
+    /// Returns a representative color palette of an image with known dimensions.
+    ///
+    /// * `width` - An image width in pixels.
+    /// * `height` - An image height in pixels.
+    ///
+    /// See `get_palette` for other arguments description.
+    pub fn build_palette_with_size(
+        &self,
+        pixels: &[u8],
+        color_format: ColorFormat,
+        width: usize,
+        height: usize,
+    ) -> Result<Vec<Color>, Error> {
+        let image = Image::with_region(pixels, color_format, width, height, Rect::new(0, 0, width, height))?;
+        let vboxes = get_vboxes(&image, self.quality, self.max_colors, &self.options)?;
+        Ok(vboxes.iter().map(|v| v.average).collect())
+    }
+}
 
 /// Returns a representative color palette of an image.
 ///
@@ -72,246 +1149,2195 @@ impl error::Error for Error {}
 /// * `quality` - Quality of an output colors.
 ///
 ///   Basically, a step in pixels to improve performance.
+///   0 chooses the step automatically to sample about 10000 pixels.
 ///
-///   Range: 1..10.
+///   Range: 0..10. `Error::InvalidQuality` otherwise.
 /// * `max_colors` - A number of colors in the output palette.
 ///   Actual colors count can be lower depending on the image.
 ///
-///   Range: 2..255.
+///   Range: 2..255. `Error::InvalidMaxColors` otherwise.
+///
+/// The first color is the most populated one, see `SortOrder::Population`.
+///
+/// See `palette` for a richer result type.
 pub fn get_palette(
     pixels: &[u8],
     color_format: ColorFormat,
     quality: u8,
     max_colors: u8,
 ) -> Result<Vec<Color>, Error> {
-    assert!(quality > 0 && quality <= 10);
-    assert!(max_colors > 1);
+    let mut colors = Vec::new();
+    get_palette_into(pixels, color_format, quality, max_colors, &mut colors)?;
+    Ok(colors)
+}
+
+/// Writes a representative color palette of an image into `out`.
+///
+/// `out` is cleared first and stays empty on error.
+/// Reusing it across calls avoids allocating an output vector each time.
+/// See `Histogram::quantize_into` to reuse a histogram as well.
+///
+/// See `get_palette` for arguments description.
+pub fn get_palette_into(
+    pixels: &[u8],
+    color_format: ColorFormat,
+    quality: u8,
+    max_colors: u8,
+    out: &mut Vec<Color>,
+) -> Result<(), Error> {
+    out.clear();
+    let vboxes = get_vboxes(&Image::checked(pixels, color_format)?, quality, max_colors, &Options::default())?;
+    out.extend(vboxes.iter().map(|v| v.average));
+    Ok(())
+}
+
+/// Returns a representative color palette of an image using the specified options.
+///
+/// See `get_palette` for arguments description.
+pub fn get_palette_with_options(
+    pixels: &[u8],
+    color_format: ColorFormat,
+    quality: u8,
+    max_colors: u8,
+    options: &Options,
+) -> Result<Vec<Color>, Error> {
+    let vboxes = get_vboxes(&Image::checked(pixels, color_format)?, quality, max_colors, options)?;
+    Ok(vboxes.iter().map(|v| v.average).collect())
+}
 
-    quantize(&pixels, color_format, quality, max_colors)
+/// Returns a representative color palette of an image as flat R, G, B bytes.
+///
+/// Easier to pass across FFI and WASM boundaries than `Vec<Color>`.
+///
+/// See `get_palette` for arguments description.
+pub fn get_palette_flat(
+    pixels: &[u8],
+    color_format: ColorFormat,
+    quality: u8,
+    max_colors: u8,
+) -> Result<Vec<u8>, Error> {
+    let colors = get_palette(pixels, color_format, quality, max_colors)?;
+    Ok(rgb::bytemuck::cast_slice(&colors).to_vec())
 }
 
-enum ColorChannel {
-    Red,
-    Green,
-    Blue,
+/// Returns a representative color palette of an image as normalized 0..1 floats.
+///
+/// See `color::to_f32` for details and `get_palette` for arguments description.
+pub fn get_palette_f32(
+    pixels: &[u8],
+    color_format: ColorFormat,
+    quality: u8,
+    max_colors: u8,
+) -> Result<Vec<[f32; 3]>, Error> {
+    let colors = get_palette(pixels, color_format, quality, max_colors)?;
+    Ok(colors.iter().map(color::to_f32).collect())
 }
 
-#[derive(Clone)]
-struct VBox {
-    r_min: u8,
-    r_max: u8,
-    g_min: u8,
-    g_max: u8,
-    b_min: u8,
-    b_max: u8,
-    average: Color,
-    volume: i32,
-    count: i32,
+/// Returns a representative color palette of an image stored as RGB pixels.
+///
+/// See `get_palette` for arguments description.
+pub fn get_palette_rgb(
+    pixels: &[Color],
+    quality: u8,
+    max_colors: u8,
+) -> Result<Vec<Color>, Error> {
+    get_palette(rgb::bytemuck::cast_slice(pixels), ColorFormat::Rgb, quality, max_colors)
+}
+
+/// Returns a representative color palette of an image stored as RGBA pixels.
+///
+/// See `get_palette` for arguments description.
+pub fn get_palette_rgba(
+    pixels: &[RGBA8],
+    quality: u8,
+    max_colors: u8,
+) -> Result<Vec<Color>, Error> {
+    get_palette(rgb::bytemuck::cast_slice(pixels), ColorFormat::Rgba, quality, max_colors)
+}
+
+/// Returns a representative color palette of a precomputed histogram.
+///
+/// The same as `Histogram::quantize`.
+///
+/// See `get_palette` for arguments description.
+pub fn quantize_histogram(histogram: &Histogram, max_colors: u8) -> Result<Vec<Color>, Error> {
+    histogram.quantize(max_colors)
+}
+
+/// Returns a representative color palette of colors yielded by an iterator.
+///
+/// Unlike `get_palette`, `quality` is a step in yielded colors.
+///
+/// The zero quality picks a step using a lower bound of `Iterator::size_hint`,
+/// which is exact only for an `ExactSizeIterator`.
+/// Adapters like `filter` or `chain` report a zero lower bound,
+/// so every yielded color is sampled then.
+///
+/// See `get_palette` for arguments description.
+pub fn get_palette_from_iter<I: IntoIterator<Item = Color>>(
+    colors: I,
+    quality: u8,
+    max_colors: u8,
+) -> Result<Vec<Color>, Error> {
+    check_palette_args(quality, max_colors)?;
+
+    // A zero lower bound gives a step of 1, so unknown lengths are sampled fully.
+    let colors = colors.into_iter();
+    let step = if quality == 0 { auto_step(colors.size_hint().0) } else { quality as usize };
+    let mut histogram = Histogram::new();
+    for c in colors.step_by(step) {
+        histogram.add_pixel(c.r, c.g, c.b, 255, None);
+    }
+
+    let vboxes = quantize(&histogram, max_colors)?;
+    Ok(vboxes.iter().map(|v| v.average).collect())
+}
+
+/// Returns a representative color palette of an indexed image.
+///
+/// Each pixel is an index into `palette`, like in GIF or indexed PNG images.
+///
+/// * `indices` - Packed pixel indices, the most significant bits first.
+///   Each row starts at a new byte, like in PNG, and the row padding bits are skipped.
+///
+///   `Error::InvalidLength` will be returned if the length is not a multiple of the row size.
+/// * `bits_per_index` - A size of an index in bits.
+///
+///   1, 2, 4 or 8. `Error::InvalidBitDepth` otherwise.
+/// * `width` - An image width in pixels.
+///
+///   `Error::InvalidLength` will be returned if it's zero.
+/// * `palette` - Colors of indices.
+///
+///   `Error::InvalidIndex` will be returned if an index is out of range.
+///
+/// Unlike `get_palette`, `quality` is a step in pixels.
+///
+/// See `get_palette` for other arguments description.
+pub fn get_palette_indexed(
+    indices: &[u8],
+    bits_per_index: u8,
+    width: u32,
+    palette: &[Color],
+    quality: u8,
+    max_colors: u8,
+) -> Result<Vec<Color>, Error> {
+    check_palette_args(quality, max_colors)?;
+
+    if !matches!(bits_per_index, 1 | 2 | 4 | 8) {
+        return Err(Error::InvalidBitDepth);
+    }
+
+    let width = width as usize;
+    let row_size = (width * bits_per_index as usize).div_ceil(8);
+    if width == 0 || !indices.len().is_multiple_of(row_size) {
+        return Err(Error::InvalidLength);
+    }
+
+    let mask = ((1u16 << bits_per_index) - 1) as u8;
+    let pixel_count = indices.len() / row_size * width;
+    let step = if quality == 0 { auto_step(pixel_count) } else { quality as usize };
+    let mut histogram = Histogram::new();
+    let mut n = 0;
+    for row in indices.chunks(row_size) {
+        for x in 0..width {
+            let bit = x * bits_per_index as usize;
+            let index = (row[bit / 8] >> (8 - bits_per_index as usize - bit % 8)) & mask;
+            let c = palette.get(index as usize).ok_or(Error::InvalidIndex)?;
+            if n % step == 0 {
+                histogram.add_pixel(c.r, c.g, c.b, 255, None);
+            }
+
+            n += 1;
+        }
+    }
+
+    let vboxes = quantize(&histogram, max_colors)?;
+    Ok(vboxes.iter().map(|v| v.average).collect())
+}
+
+/// Returns a single representative color palette of multiple images.
+///
+/// All images are collected into the same histogram before quantization,
+/// so larger images have more influence on the palette.
+/// Images can have different color formats.
+///
+/// See `get_palette` for arguments description.
+pub fn get_palette_multi(
+    images: &[(&[u8], ColorFormat)],
+    quality: u8,
+    max_colors: u8,
+) -> Result<Vec<Color>, Error> {
+    check_palette_args(quality, max_colors)?;
+
+    if images.iter().all(|&(pixels, _)| pixels.is_empty()) {
+        return Err(Error::EmptyImage);
+    }
+
+    let mut histogram = Histogram::new();
+    for &(pixels, color_format) in images {
+        histogram.add_frame(pixels, color_format, quality)?;
+    }
+
+    histogram.quantize(max_colors)
+}
+
+/// Returns a representative color palette of an image stored as separate R, G and B planes.
+///
+/// Produces the same palette as `get_palette` with the same pixels in `ColorFormat::Rgb`.
+/// `Error::PlaneLengthMismatch` will be returned if planes have different lengths.
+///
+/// See `get_palette` for arguments description.
+pub fn get_palette_planar(
+    r: &[u8],
+    g: &[u8],
+    b: &[u8],
+    quality: u8,
+    max_colors: u8,
+) -> Result<Vec<Color>, Error> {
+    if r.len() != g.len() || r.len() != b.len() {
+        return Err(Error::PlaneLengthMismatch);
+    }
+
+    check_palette_args(quality, max_colors)?;
+
+    if r.is_empty() {
+        return Err(Error::EmptyImage);
+    }
+
+    // The same step as for RGB.
+    let step = if quality == 0 { auto_step(r.len()) } else { 3 * quality as usize };
+    let mut histogram = Histogram::new();
+    for i in (0..r.len()).step_by(step) {
+        histogram.add_pixel(r[i], g[i], b[i], 255, None);
+    }
+
+    let vboxes = quantize(&histogram, max_colors)?;
+    Ok(vboxes.iter().map(|v| v.average).collect())
+}
+
+/// Returns a representative color palette of an image with a custom pixel layout.
+///
+/// * `bytes_per_pixel` - A size of a single pixel.
+///
+///   `Error::InvalidLength` will be returned if it's zero
+///   or the buffer length is not a multiple of it.
+/// * `extractor` - Returns r, g, b, a color parts of a pixel
+///   at the specified byte offset of `pixels`.
+///
+/// Pixels are sampled just like in `get_palette`, with `bytes_per_pixel` as a number of channels.
+///
+/// See `get_palette` for other arguments description.
+pub fn get_palette_with<F: Fn(&[u8], usize) -> (u8, u8, u8, u8)>(
+    pixels: &[u8],
+    bytes_per_pixel: usize,
+    extractor: F,
+    quality: u8,
+    max_colors: u8,
+) -> Result<Vec<Color>, Error> {
+    if bytes_per_pixel == 0 || !pixels.len().is_multiple_of(bytes_per_pixel) {
+        return Err(Error::InvalidLength);
+    }
+
+    check_palette_args(quality, max_colors)?;
+
+    if pixels.is_empty() {
+        return Err(Error::EmptyImage);
+    }
+
+    let mut histogram = Histogram::new();
+    let pixel_count = pixels.len() / bytes_per_pixel;
+    let step = if quality == 0 { auto_step(pixel_count) } else { bytes_per_pixel * quality as usize };
+    for index in (0..pixel_count).step_by(step) {
+        let (r, g, b, a) = extractor(pixels, index * bytes_per_pixel);
+        histogram.add_pixel(r, g, b, a, None);
+    }
+
+    histogram.quantize(max_colors)
+}
+
+/// Returns a representative color palette of an image using a fraction of its pixels.
+///
+/// * `fraction` - A fraction of pixels to sample.
+///
+///   Unlike `quality`, makes the performance independent of the image resolution.
+///
+///   Range: 0..1, excluding 0. `Error::InvalidFraction` otherwise.
+///
+/// See `get_palette` for other arguments description.
+pub fn get_palette_with_fraction(
+    pixels: &[u8],
+    color_format: ColorFormat,
+    fraction: f32,
+    max_colors: u8,
+) -> Result<Vec<Color>, Error> {
+    if !(fraction > 0.0 && fraction <= 1.0) {
+        return Err(Error::InvalidFraction);
+    }
+
+    let image = Image::checked(pixels, color_format)?;
+    let step = fraction_step(&image, fraction);
+    let vboxes = get_vboxes_with_step(&image, step, max_colors, &Options::default(), false)?;
+    Ok(vboxes.iter().map(|v| v.average).collect())
+}
+
+/// Returns a representative color palette of an image
+/// with a number of pixels represented by each color.
+///
+/// Colors are in the same order as in `get_palette`.
+///
+/// See `get_palette` for arguments description.
+pub fn get_palette_with_counts(
+    pixels: &[u8],
+    color_format: ColorFormat,
+    quality: u8,
+    max_colors: u8,
+) -> Result<Vec<(Color, u64)>, Error> {
+    let vboxes = get_vboxes(&Image::checked(pixels, color_format)?, quality, max_colors, &Options::default())?;
+    Ok(vboxes.iter().map(|v| (v.average, v.count)).collect())
+}
+
+/// Returns a representative color palette of an image
+/// with a fraction of sampled pixels represented by each color.
+///
+/// Fractions are in a 0..1 range and sum up to 1.
+/// Colors are in the same order as in `get_palette`.
+///
+/// See `get_palette` for arguments description.
+pub fn get_palette_with_weights(
+    pixels: &[u8],
+    color_format: ColorFormat,
+    quality: u8,
+    max_colors: u8,
+) -> Result<Vec<(Color, f32)>, Error> {
+    let vboxes = get_vboxes(&Image::checked(pixels, color_format)?, quality, max_colors, &Options::default())?;
+    let total: u64 = vboxes.iter().map(|v| v.count).sum();
+    Ok(vboxes.iter().map(|v| (v.average, (v.count as f64 / total as f64) as f32)).collect())
+}
+
+/// Returns a number of distinct colors of an image.
+///
+/// Colors are counted with the histogram precision, i.e. as cells occupied by sampled pixels.
+/// Skipped pixels are not counted.
+/// Useful to choose a sensible `max_colors`.
+///
+/// See `get_palette` for arguments description.
+pub fn distinct_color_count(
+    pixels: &[u8],
+    color_format: ColorFormat,
+    quality: u8,
+) -> Result<u32, Error> {
+    check_quality(quality)?;
+
+    let image = Image::checked(pixels, color_format)?;
+    let mut histogram = Histogram::new();
+    histogram.fill(&image, quality_step(&image, quality, CompatMode::Default))?;
+    Ok(histogram.data.iter().filter(|&&c| c != 0).count() as u32)
+}
+
+/// Checks that an image is effectively grayscale.
+///
+/// An image is grayscale when channels of almost all sampled pixels,
+/// except for 1% of outliers, differ by at most `tolerance`.
+/// Pixels are sampled just like in `get_palette` and transparent ones are skipped.
+///
+/// See `get_palette` for other arguments description.
+pub fn is_grayscale(
+    pixels: &[u8],
+    color_format: ColorFormat,
+    quality: u8,
+    tolerance: u8,
+) -> Result<bool, Error> {
+    check_quality(quality)?;
+
+    let image = Image::checked(pixels, color_format)?;
+    let step = quality_step(&image, quality, CompatMode::Default);
+    // White is gray too.
+    let options = Options { ignore_white: None, ..Options::default() };
+
+    let mut total = 0u64;
+    let mut colored = 0u64;
+    for (r, g, b, _) in sampled_pixels(&image, step, &options) {
+        let max = cmp::max(r, cmp::max(g, b));
+        let min = cmp::min(r, cmp::min(g, b));
+        total += 1;
+        if max - min > tolerance {
+            colored += 1;
+        }
+    }
+
+    if total == 0 {
+        return Err(Error::EmptyImage);
+    }
+
+    Ok(colored as f32 <= GRAYSCALE_OUTLIERS * total as f32)
+}
+
+/// Returns a distribution of hues of an image.
+///
+/// Sampled pixels are split into `bins` equal hue ranges, starting from red,
+/// and weighted by saturation, so gray pixels do not contribute.
+/// Values are normalized to sum up to 1, unless all pixels are gray.
+/// Pixels are sampled and skipped just like in `get_palette`.
+///
+/// See `get_palette` for other arguments description.
+pub fn hue_histogram(
+    pixels: &[u8],
+    color_format: ColorFormat,
+    quality: u8,
+    bins: usize,
+) -> Result<Vec<f32>, Error> {
+    check_quality(quality)?;
+
+    let image = Image::checked(pixels, color_format)?;
+    let step = quality_step(&image, quality, CompatMode::Default);
+    let options = Options::default();
+
+    let mut histogram = vec![0.0f32; bins];
+    if bins == 0 {
+        return Ok(histogram);
+    }
+
+    for (r, g, b, _) in sampled_pixels(&image, step, &options) {
+        let (h, s, _) = color::to_hsl(&Color::new(r, g, b));
+        let bin = cmp::min((h / 360.0 * bins as f32) as usize, bins - 1);
+        histogram[bin] += s;
+    }
+
+    let total: f32 = histogram.iter().sum();
+    if total > 0.0 {
+        for v in &mut histogram {
+            *v /= total;
+        }
+    }
+
+    Ok(histogram)
+}
+
+/// Returns how colorful an image is, in a 0..1 range.
+///
+/// The score is an average saturation of sampled pixels, weighted by saturation,
+/// so a few vivid regions are not lost among gray ones.
+/// Pixels are sampled and skipped just like in `get_palette`.
+///
+/// See `get_palette` for arguments description.
+pub fn vividness(
+    pixels: &[u8],
+    color_format: ColorFormat,
+    quality: u8,
+) -> Result<f32, Error> {
+    check_quality(quality)?;
+
+    let image = Image::checked(pixels, color_format)?;
+    let step = quality_step(&image, quality, CompatMode::Default);
+    let options = Options::default();
+
+    let mut weighted_sum = 0.0f64;
+    let mut weight_sum = 0.0f64;
+    for (r, g, b, _) in sampled_pixels(&image, step, &options) {
+        let (_, s, _) = color::to_hsl(&Color::new(r, g, b));
+        weighted_sum += (s * s) as f64;
+        weight_sum += s as f64;
+    }
+
+    if weight_sum == 0.0 {
+        return Ok(0.0);
+    }
+
+    Ok((weighted_sum / weight_sum) as f32)
+}
+
+/// Returns how warm an image is, in a -1..1 range.
+///
+/// Reds and oranges are positive, blues are negative.
+/// The score is an average warmth of pixel hues, weighted by saturation,
+/// so a saturated orange image is close to 1 and a gray one is 0.
+/// Pixels are sampled and skipped just like in `get_palette`.
+///
+/// See `get_palette` for arguments description.
+pub fn color_temperature(
+    pixels: &[u8],
+    color_format: ColorFormat,
+    quality: u8,
+) -> Result<f32, Error> {
+    check_quality(quality)?;
+
+    let image = Image::checked(pixels, color_format)?;
+    let step = quality_step(&image, quality, CompatMode::Default);
+    let options = Options::default();
+
+    let mut warmth_sum = 0.0f64;
+    let mut count = 0u64;
+    for (r, g, b, _) in sampled_pixels(&image, step, &options) {
+        let (h, s, _) = color::to_hsl(&Color::new(r, g, b));
+        warmth_sum += (s * color::hue_warmth(h)) as f64;
+        count += 1;
+    }
+
+    if count == 0 {
+        return Ok(0.0);
+    }
+
+    Ok((warmth_sum / count as f64) as f32)
+}
+
+fn get_vboxes<T: Channel>(
+    image: &Image<T>,
+    quality: u8,
+    max_colors: u8,
+    options: &Options,
+) -> Result<Vec<VBox>, Error> {
+    check_quality(quality)?;
+
+    let step = quality_step(image, quality, options.compat_mode);
+    get_vboxes_with_step(image, step, max_colors, options, false)
+}
+
+fn get_vboxes_with_step<T: Channel>(
+    image: &Image<T>,
+    step: usize,
+    max_colors: u8,
+    options: &Options,
+    alpha: bool,
+) -> Result<Vec<VBox>, Error> {
+    let histogram = get_histogram(image, step, max_colors, options, alpha)?;
+    quantize(&histogram, max_colors)
+}
+
+/// `alpha` enables alpha tracking, see `VBox::alpha`.
+fn get_histogram<T: Channel>(
+    image: &Image<T>,
+    step: usize,
+    max_colors: u8,
+    options: &Options,
+    alpha: bool,
+) -> Result<Histogram, Error> {
+    check_max_colors(max_colors)?;
+
+    if image.pixels.is_empty() {
+        return Err(Error::EmptyImage);
+    }
+
+    let mut histogram = Histogram::with_options(*options)?;
+    if alpha {
+        histogram.alpha = vec![0; histogram.data.len()];
+    }
+
+    histogram.fill(image, step)?;
+    Ok(histogram)
+}
+
+/// Returns a representative color palette of an image with padded rows.
+///
+/// * `width` - An image width in pixels.
+/// * `stride` - A row length in bytes, including padding.
+///   Must be greater or equal to `width` multiplied by the number of bytes per pixel.
+///
+/// See `get_palette` for other arguments description.
+pub fn get_palette_with_stride(
+    pixels: &[u8],
+    color_format: ColorFormat,
+    width: usize,
+    stride: usize,
+    quality: u8,
+    max_colors: u8,
+) -> Result<Vec<Color>, Error> {
+    let image = Image::with_stride(pixels, color_format, width, stride)?;
+    let vboxes = get_vboxes(&image, quality, max_colors, &Options::default())?;
+    Ok(vboxes.iter().map(|v| v.average).collect())
+}
+
+/// Returns a representative color palette of masked pixels of an image.
+///
+/// * `mask` - One value per pixel. Pixels with a zero value will be skipped.
+///
+///   `Error::InvalidMask` will be returned when the length doesn't match the number of pixels.
+///
+/// See `get_palette` for other arguments description.
+pub fn get_palette_with_mask(
+    pixels: &[u8],
+    color_format: ColorFormat,
+    mask: &[u8],
+    quality: u8,
+    max_colors: u8,
+) -> Result<Vec<Color>, Error> {
+    let image = Image::checked(pixels, color_format)?.with_mask(mask)?;
+    let vboxes = get_vboxes(&image, quality, max_colors, &Options::default())?;
+    Ok(vboxes.iter().map(|v| v.average).collect())
+}
+
+/// Returns a representative color palette of an image region.
+///
+/// * `width` - An image width in pixels.
+/// * `height` - An image height in pixels.
+/// * `region` - A region to process. Will be clamped to the image bounds.
+///   `Error::EmptyRegion` will be returned if nothing is left.
+///
+/// See `get_palette` for other arguments description.
+pub fn get_palette_region(
+    pixels: &[u8],
+    color_format: ColorFormat,
+    width: usize,
+    height: usize,
+    region: Rect,
+    quality: u8,
+    max_colors: u8,
+) -> Result<Vec<Color>, Error> {
+    let image = Image::with_region(pixels, color_format, width, height, region)?;
+    let vboxes = get_vboxes(&image, quality, max_colors, &Options::default())?;
+    Ok(vboxes.iter().map(|v| v.average).collect())
+}
+
+/// Returns a representative color palette of an image with 16 bits per channel.
+///
+/// Only the upper bits of each channel are used, so output colors
+/// are the same as for the same image converted to 8 bits per channel.
+///
+/// * `pixels` - A raw image data, one `u16` per channel.
+///
+/// See `get_palette` for other arguments description.
+pub fn get_palette_u16(
+    pixels: &[u16],
+    color_format: ColorFormat,
+    quality: u8,
+    max_colors: u8,
+) -> Result<Vec<Color>, Error> {
+    let vboxes = get_vboxes(&Image::checked(pixels, color_format)?, quality, max_colors, &Options::default())?;
+    Ok(vboxes.iter().map(|v| v.average).collect())
+}
+
+/// Returns the dominant color of an image.
+///
+/// A shorthand for `get_palette` with `max_colors` set to 5,
+/// which returns the first, most populated color of the palette.
+///
+/// * `pixels` - A raw image data.
+/// * `color_format` - Represent a color format of an underlying image data.
+/// * `quality` - Quality of an output color. See `get_palette` for details.
+pub fn get_dominant_color(
+    pixels: &[u8],
+    color_format: ColorFormat,
+    quality: u8,
+) -> Result<Color, Error> {
+    let colors = get_palette(pixels, color_format, quality, 5)?;
+    colors.first().cloned().ok_or(Error::EmptyPalette)
+}
+
+/// Returns the most common color of the image border.
+///
+/// The border is 5% of the smallest image side, but at least 1 pixel wide.
+/// Falls back to the dominant color when the border covers the whole image.
+///
+/// * `width` - An image width in pixels.
+/// * `height` - An image height in pixels.
+///
+/// See `get_palette` for other arguments description.
+pub fn get_background_color(
+    pixels: &[u8],
+    color_format: ColorFormat,
+    width: usize,
+    height: usize,
+    quality: u8,
+) -> Result<Color, Error> {
+    check_quality(quality)?;
+
+    // Do not trust `height` if there is not enough data.
+    let height = if width != 0 {
+        cmp::min(height, pixels.len() / width.saturating_mul(color_format.channels()))
+    } else {
+        0
+    };
+
+    if width == 0 || height == 0 {
+        return Err(Error::EmptyImage);
+    }
+
+    let border = cmp::max(cmp::min(width, height) / 20, 1);
+    if border * 2 >= width || border * 2 >= height {
+        return get_dominant_color(&pixels[..width * height * color_format.channels()], color_format, quality);
+    }
+
+    let options = Options::default();
+    let mut histogram = Histogram::empty(options);
+    let regions = [
+        Rect::new(0, 0, width, border),
+        Rect::new(0, height - border, width, border),
+        Rect::new(0, border, border, height - border * 2),
+        Rect::new(width - border, border, border, height - border * 2),
+    ];
+    for region in &regions {
+        let image = Image::with_region(pixels, color_format, width, height, *region)?;
+        let step = quality_step(&image, quality, options.compat_mode);
+        histogram.fill(&image, step)?;
+    }
+
+    let vboxes = quantize(&histogram, 5)?;
+    vboxes.first().map(|v| v.average).ok_or(Error::EmptyPalette)
+}
+
+/// Returns a representative color palette of an image
+/// and an index of the closest palette color for each pixel.
+///
+/// Pixels skipped during quantization, like transparent ones,
+/// are mapped to the closest color as well.
+///
+/// See `get_palette` for arguments description.
+pub fn get_indexed(
+    pixels: &[u8],
+    color_format: ColorFormat,
+    quality: u8,
+    max_colors: u8,
+) -> Result<(Vec<Color>, Vec<u8>), Error> {
+    let image = Image::checked(pixels, color_format)?;
+    let vboxes = get_vboxes(&image, quality, max_colors, &Options::default())?;
+    let palette: Vec<Color> = vboxes.iter().map(|v| v.average).collect();
+
+    let mut indices = Vec::with_capacity(image.pixel_count);
+    for i in 0..image.pixel_count {
+        let (r, g, b, _) = image.pixel(i);
+        // The palette has at most 255 colors and is not empty.
+        let idx = nearest_color(&palette, Color::new(r, g, b)).unwrap_or(0);
+        indices.push(idx as u8);
+    }
+
+    Ok((palette, indices))
+}
+
+/// Returns an index of the palette color closest to the specified one.
+///
+/// Uses a squared Euclidean distance in RGB space.
+/// The first color wins when distances are equal.
+///
+/// Returns `None` when the palette is empty.
+pub fn nearest_color(palette: &[Color], color: Color) -> Option<usize> {
+    palette.iter()
+        .enumerate()
+        .min_by_key(|&(_, c)| distance_sq(*c, color))
+        .map(|(i, _)| i)
+}
+
+/// Returns how different two palettes are, in a 0..1 range.
+///
+/// Colors are matched greedily, the closest pair first, and each color is used once.
+/// Colors left in a longer palette are matched with their nearest color in a shorter one.
+/// The result is a mean RGB distance between matched colors, divided by the distance
+/// between black and white, so equal palettes are 0 and opposite ones are close to 1.
+///
+/// The order of colors doesn't matter.
+/// Returns 0 when both palettes are empty and 1 when only one of them is.
+pub fn palette_distance(a: &[Color], b: &[Color]) -> f32 {
+    if a.is_empty() || b.is_empty() {
+        return if a.len() == b.len() { 0.0 } else { 1.0 };
+    }
+
+    let (short, long) = if a.len() <= b.len() { (a, b) } else { (b, a) };
+
+    let mut pairs = Vec::with_capacity(short.len() * long.len());
+    for (i, c1) in short.iter().enumerate() {
+        for (j, c2) in long.iter().enumerate() {
+            pairs.push((distance_sq(*c1, *c2), i, j));
+        }
+    }
+    pairs.sort_unstable();
+
+    let mut short_used = vec![false; short.len()];
+    let mut long_used = vec![false; long.len()];
+    let mut sum = 0.0f32;
+    let mut matched = 0;
+    for &(d, i, j) in &pairs {
+        if matched == short.len() {
+            break;
+        }
+
+        if !short_used[i] && !long_used[j] {
+            short_used[i] = true;
+            long_used[j] = true;
+            sum += color::sqrt(d as f32);
+            matched += 1;
+        }
+    }
+
+    for (j, c) in long.iter().enumerate() {
+        if !long_used[j] {
+            let d = short.iter().map(|c2| distance_sq(*c, *c2)).min().unwrap_or(0);
+            sum += color::sqrt(d as f32);
+        }
+    }
+
+    let max_distance = color::sqrt(distance_sq(Color::new(0, 0, 0), Color::new(255, 255, 255)) as f32);
+    sum / (long.len() as f32 * max_distance)
+}
+
+/// Returns colors of a fixed palette with a number of image pixels closest to each of them.
+///
+/// Unlike `get_palette`, no new colors are produced.
+/// Each sampled pixel is counted by the nearest `allowed` color, see `nearest_color`.
+/// Skipped pixels, like transparent ones, are not counted.
+///
+/// Returns all `allowed` colors, the most populated first.
+/// Colors with equal populations keep their order.
+///
+/// `Error::EmptyPalette` will be returned if `allowed` is empty.
+///
+/// See `get_palette` for other arguments description.
+pub fn constrained_palette(
+    pixels: &[u8],
+    color_format: ColorFormat,
+    quality: u8,
+    allowed: &[Color],
+) -> Result<Vec<(Color, u64)>, Error> {
+    check_quality(quality)?;
+
+    if allowed.is_empty() {
+        return Err(Error::EmptyPalette);
+    }
+
+    let image = Image::checked(pixels, color_format)?;
+    if image.pixels.is_empty() {
+        return Err(Error::EmptyImage);
+    }
+
+    let options = Options::default();
+    let step = quality_step(&image, quality, options.compat_mode);
+    let mut counts = vec![0u64; allowed.len()];
+    for (r, g, b, _) in sampled_pixels(&image, step, &options) {
+        let i = nearest_color(allowed, Color::new(r, g, b)).unwrap_or(0);
+        counts[i] += 1;
+    }
+
+    let mut colors: Vec<_> = allowed.iter().cloned().zip(counts).collect();
+    colors.sort_by_key(|c| cmp::Reverse(c.1));
+    Ok(colors)
+}
+
+/// A color of pixels in a histogram cell, packed into `u32`.
+mod exact_color {
+    use Color;
+
+    /// A cell without pixels.
+    pub const NONE: u32 = 0;
+    /// A cell with pixels of different colors.
+    pub const MIXED: u32 = u32::MAX;
+
+    pub fn from(c: Color) -> u32 {
+        // Shifted, so black is not `NONE`.
+        ((c.r as u32) << 16 | (c.g as u32) << 8 | c.b as u32) + 1
+    }
+
+    pub fn to_color(v: u32) -> Option<Color> {
+        if v == NONE || v == MIXED {
+            return None;
+        }
+
+        let v = v - 1;
+        Some(Color::new((v >> 16) as u8, (v >> 8) as u8, v as u8))
+    }
+
+    pub fn merge(a: u32, b: u32) -> u32 {
+        if a == NONE || a == b {
+            b
+        } else if b == NONE {
+            a
+        } else {
+            MIXED
+        }
+    }
+}
+
+fn distance_sq(a: Color, b: Color) -> u32 {
+    let dr = a.r as i32 - b.r as i32;
+    let dg = a.g as i32 - b.g as i32;
+    let db = a.b as i32 - b.b as i32;
+    (dr * dr + dg * dg + db * db) as u32
+}
+
+/// A single channel of an underlying image data.
+trait Channel: Copy + Sync {
+    fn to_u8(self) -> u8;
+}
+
+impl Channel for u8 {
+    #[inline]
+    fn to_u8(self) -> u8 {
+        self
+    }
+}
+
+impl Channel for u16 {
+    #[inline]
+    fn to_u8(self) -> u8 {
+        (self >> 8) as u8
+    }
+}
+
+enum ColorChannel {
+    Red,
+    Green,
+    Blue,
+}
+
+#[derive(Clone)]
+struct VBox {
+    r_min: u8,
+    r_max: u8,
+    g_min: u8,
+    g_max: u8,
+    b_min: u8,
+    b_max: u8,
+    average: Color,
+    /// An average alpha. 255 when alpha is not tracked.
+    alpha: u8,
+    volume: u32,
+    count: u64,
+}
+
+impl VBox {
+    fn new(
+        r_min: u8, r_max: u8,
+        g_min: u8, g_max: u8,
+        b_min: u8, b_max: u8,
+    ) -> VBox {
+        VBox {
+            r_min,
+            r_max,
+            g_min,
+            g_max,
+            b_min,
+            b_max,
+            average: Color::new(0, 0, 0),
+            alpha: 255,
+            volume: 0,
+            count: 0,
+        }
+
+        // `recalc()` should be called right after `new()`.
+    }
+
+    fn recalc(&mut self, histogram: &Histogram) {
+        self.average = self.calc_average(histogram);
+        self.count = self.calc_count(histogram);
+        self.alpha = self.calc_alpha(histogram);
+        self.volume = self.calc_volume();
+    }
+
+    fn calc_alpha(&self, histogram: &Histogram) -> u8 {
+        if histogram.alpha.is_empty() || self.count == 0 {
+            return 255;
+        }
+
+        let mut sum = 0;
+        for i in self.r_min..=self.r_max {
+            for j in self.g_min..=self.g_max {
+                for k in self.b_min..=self.b_max {
+                    sum += histogram.get_alpha(i, j, k).unwrap_or(0);
+                }
+            }
+        }
+
+        ((sum + self.count / 2) / self.count) as u8
+    }
+
+    /// Get 3 dimensional volume of the color space.
+    fn calc_volume(&self) -> u32 {
+          (self.r_max as u32 + 1).saturating_sub(self.r_min as u32)
+        * (self.g_max as u32 + 1).saturating_sub(self.g_min as u32)
+        * (self.b_max as u32 + 1).saturating_sub(self.b_min as u32)
+    }
+
+    /// Get total count of histogram samples.
+    fn calc_count(&self, histogram: &Histogram) -> u64 {
+        let mut count = 0;
+        for i in self.r_min..=self.r_max {
+            for j in self.g_min..=self.g_max {
+                for k in self.b_min..=self.b_max {
+                    count += histogram.get(i, j, k);
+                }
+            }
+        }
+
+        count
+    }
+
+    fn calc_average(&self, histogram: &Histogram) -> Color {
+        if histogram.options.linear_average && histogram.options.color_space == ColorSpace::Rgb {
+            if let Some(c) = self.calc_linear_average(histogram) {
+                return c;
+            }
+        }
+
+        let multiplier = histogram.multiplier();
+        let multiplier_64 = multiplier as f64;
+
+        let mut ntot = 0;
+
+        let mut r_sum = 0;
+        let mut g_sum = 0;
+        let mut b_sum = 0;
+
+        for i in self.r_min..=self.r_max {
+            for j in self.g_min..=self.g_max {
+                for k in self.b_min..=self.b_max {
+                    // Most cells are empty and add nothing to the sums.
+                    let count = histogram.get(i, j, k);
+                    if count == 0 {
+                        continue;
+                    }
+
+                    let hval = count as f64;
+                    ntot += count;
+                    r_sum += (hval * (i as f64 + 0.5) * multiplier_64) as u64;
+                    g_sum += (hval * (j as f64 + 0.5) * multiplier_64) as u64;
+                    b_sum += (hval * (k as f64 + 0.5) * multiplier_64) as u64;
+                }
+            }
+        }
+
+        if ntot != 0 {
+            let div = |sum: u64| match histogram.options.rounding {
+                RoundingMode::Truncate => sum / ntot,
+                RoundingMode::Nearest => (sum + ntot / 2) / ntot,
+            };
+            Color::new(cmp::min(div(r_sum), 255) as u8,
+                       cmp::min(div(g_sum), 255) as u8,
+                       cmp::min(div(b_sum), 255) as u8)
+        } else {
+            let r = multiplier * (self.r_min as i32 + self.r_max as i32 + 1) / 2;
+            let g = multiplier * (self.g_min as i32 + self.g_max as i32 + 1) / 2;
+            let b = multiplier * (self.b_min as i32 + self.b_max as i32 + 1) / 2;
+            Color::new(cmp::min(r, 255) as u8,
+                       cmp::min(g, 255) as u8,
+                       cmp::min(b, 255) as u8)
+        }
+    }
+
+    /// Returns `None` for an empty box.
+    fn calc_linear_average(&self, histogram: &Histogram) -> Option<Color> {
+        let multiplier = histogram.multiplier();
+        let center = |i: u8| {
+            let c = i as i32 * multiplier + multiplier / 2;
+            color::srgb_to_linear(cmp::min(c, 255) as u8) as f64
+        };
+
+        let mut ntot = 0.0;
+
+        let mut r_sum = 0.0;
+        let mut g_sum = 0.0;
+        let mut b_sum = 0.0;
+
+        for i in self.r_min..=self.r_max {
+            for j in self.g_min..=self.g_max {
+                for k in self.b_min..=self.b_max {
+                    let count = histogram.get(i, j, k);
+                    if count == 0 {
+                        continue;
+                    }
+
+                    let hval = count as f64;
+                    ntot += hval;
+                    r_sum += hval * center(i);
+                    g_sum += hval * center(j);
+                    b_sum += hval * center(k);
+                }
+            }
+        }
+
+        if ntot > 0.0 {
+            Some(Color::new(color::linear_to_srgb((r_sum / ntot) as f32),
+                            color::linear_to_srgb((g_sum / ntot) as f32),
+                            color::linear_to_srgb((b_sum / ntot) as f32)))
+        } else {
+            None
+        }
+    }
+
+    fn widest_color_channel(&self) -> ColorChannel {
+        let r_width = self.r_max - self.r_min;
+        let g_width = self.g_max - self.g_min;
+        let b_width = self.b_max - self.b_min;
+
+        let max = cmp::max(cmp::max(r_width, g_width), b_width);
+
+        if max == r_width {
+            ColorChannel::Red
+        } else if max == g_width {
+            ColorChannel::Green
+        } else {
+            ColorChannel::Blue
+        }
+    }
+}
+
+/// An image data view.
+struct Image<'a, T: 'a> {
+    pixels: &'a [T],
+    color_format: ColorFormat,
+    /// Image width in pixels.
+    width: usize,
+    /// Row length in channels.
+    stride: usize,
+    /// Position of the first pixel in channels.
+    offset: usize,
+    pixel_count: usize,
+    /// Pixels with a zero mask value will be skipped.
+    mask: Option<&'a [u8]>,
+    /// Image dimensions are known. Otherwise, the image is a single row.
+    sized: bool,
+}
+
+impl<'a, T: Channel> Image<'a, T> {
+    /// Creates a view of a tightly packed image data,
+    /// checking that it contains only whole pixels.
+    fn checked(pixels: &'a [T], color_format: ColorFormat) -> Result<Self, Error> {
+        if !pixels.len().is_multiple_of(color_format.channels()) {
+            return Err(Error::InvalidLength);
+        }
+
+        Ok(Image::packed(pixels, color_format))
+    }
+
+    /// Creates a view of a tightly packed image data.
+    ///
+    /// A partial trailing pixel is ignored.
+    fn packed(pixels: &'a [T], color_format: ColorFormat) -> Self {
+        let pixel_count = pixels.len() / color_format.channels();
+        Image {
+            pixels,
+            color_format,
+            width: pixel_count,
+            stride: pixel_count * color_format.channels(),
+            offset: 0,
+            pixel_count,
+            mask: None,
+            sized: false,
+        }
+    }
+
+    /// Creates a view of an image data with padded rows.
+    ///
+    /// The last row is allowed to have no padding.
+    fn with_stride(
+        pixels: &'a [T],
+        color_format: ColorFormat,
+        width: usize,
+        stride: usize,
+    ) -> Result<Self, Error> {
+        let row_len = width.saturating_mul(color_format.channels());
+        if width == 0 || stride < row_len {
+            return Err(Error::InvalidStride);
+        }
+
+        let height = if pixels.len() >= row_len {
+            (pixels.len() - row_len) / stride + 1
+        } else {
+            0
+        };
+
+        Ok(Image {
+            pixels,
+            color_format,
+            width,
+            stride,
+            offset: 0,
+            pixel_count: width * height,
+            mask: None,
+            sized: true,
+        })
+    }
+
+    /// Creates a view of a rectangular region of a tightly packed image data.
+    ///
+    /// The region is clamped to the image bounds.
+    fn with_region(
+        pixels: &'a [T],
+        color_format: ColorFormat,
+        width: usize,
+        height: usize,
+        region: Rect,
+    ) -> Result<Self, Error> {
+        let channels = color_format.channels();
+
+        // Do not trust `height` if there is not enough data.
+        let height = if width != 0 {
+            cmp::min(height, pixels.len() / width.saturating_mul(channels))
+        } else {
+            0
+        };
+
+        let x = cmp::min(region.x, width);
+        let y = cmp::min(region.y, height);
+        let w = cmp::min(region.width, width - x);
+        let h = cmp::min(region.height, height - y);
+        if w == 0 || h == 0 {
+            return Err(Error::EmptyRegion);
+        }
+
+        Ok(Image {
+            pixels,
+            color_format,
+            width: w,
+            stride: width * channels,
+            offset: (y * width + x) * channels,
+            pixel_count: w * h,
+            mask: None,
+            sized: true,
+        })
+    }
+
+    /// Sets a mask with one value per pixel.
+    fn with_mask(mut self, mask: &'a [u8]) -> Result<Self, Error> {
+        if mask.len() != self.pixel_count {
+            return Err(Error::InvalidMask);
+        }
+
+        self.mask = Some(mask);
+        Ok(self)
+    }
+
+    /// Returns r, g, b, a color parts of a pixel with the specified index.
+    #[inline]
+    fn pixel(&self, index: usize) -> (u8, u8, u8, u8) {
+        let channels = self.color_format.channels();
+        let pos = if self.stride == self.width * channels {
+            self.offset + index * channels
+        } else {
+            self.offset + (index / self.width) * self.stride + (index % self.width) * channels
+        };
+
+        color_parts(self.pixels, self.color_format, pos)
+    }
+}
+
+/// A final box of the color space chosen by quantization.
+///
+/// See `Histogram::quantize_debug`.
+#[derive(Clone,Copy,PartialEq,Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct VBoxInfo {
+    /// An average color of the box. The same as the palette color.
+    pub average: Color,
+    /// A number of histogram cells in the box.
+    pub volume: u32,
+    /// A number of pixels in the box.
+    ///
+    /// A sum of pixel weights, rounded, when pixels are weighted.
+    /// See `Palette::counts`.
+    pub count: u64,
+    /// Minimum reduced r, g, b values, in the same units as `Histogram::bounds`.
+    pub min: [u8; 3],
+    /// Maximum reduced r, g, b values, in the same units as `Histogram::bounds`.
+    pub max: [u8; 3],
+}
+
+/// A color histogram of one or more images.
+///
+/// Can be reused between video frames to avoid allocations.
+///
+/// # Example
+///
+/// ```no_run
+/// # let frames: Vec<Vec<u8>> = Vec::new();
+/// use color_thief::{ColorFormat, Histogram};
+///
+/// let mut histogram = Histogram::new();
+/// for frame in &frames {
+///     histogram.clear();
+///     histogram.add_frame(frame, ColorFormat::Rgb, 10).unwrap();
+///     let colors = histogram.quantize(10).unwrap();
+/// }
+/// ```
+#[derive(Clone)]
+pub struct Histogram {
+    data: Vec<u64>,
+    /// Sums of alpha values per cell. Empty when alpha is not tracked.
+    alpha: Vec<u64>,
+    /// Colors of pixels per cell. Empty unless `Options::snap_exact` is set.
+    ///
+    /// See `exact_color`.
+    exact: Vec<u32>,
+    r_min: u8,
+    r_max: u8,
+    g_min: u8,
+    g_max: u8,
+    b_min: u8,
+    b_max: u8,
+    /// A number of skipped transparent pixels.
+    transparent: u64,
+    /// Sums of r, g, b, a values of skipped transparent pixels.
+    /// Zeros unless `Options::transparent_average` is set.
+    transparent_sum: [u64; 4],
+    /// A number of skipped white pixels.
+    white: u64,
+    /// Pixels are weighted, so counts are in `1 / WEIGHT_SCALE` units.
+    weighted: bool,
+    options: Options,
+}
+
+impl fmt::Debug for Histogram {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Histogram")
+            .field("bounds", &self.bounds())
+            .field("options", &self.options)
+            .finish()
+    }
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Histogram::empty(Options::default())
+    }
+}
+
+impl Histogram {
+    /// Creates a new, empty histogram with default options.
+    pub fn new() -> Self {
+        Histogram::default()
+    }
+
+    /// Creates a new, empty histogram with the specified options.
+    pub fn with_options(options: Options) -> Result<Self, Error> {
+        if options.signal_bits == 0 || options.signal_bits > 8 {
+            return Err(Error::InvalidSignalBits);
+        }
+
+        if !(options.saturation_weight >= 0.0 && options.saturation_weight <= MAX_SATURATION_WEIGHT) {
+            return Err(Error::InvalidSaturationWeight);
+        }
+
+        if !(options.population_fraction >= 0.0 && options.population_fraction <= 1.0) {
+            return Err(Error::InvalidPopulationFraction);
+        }
+
+        if !(options.min_population >= 0.0 && options.min_population <= 1.0) {
+            return Err(Error::InvalidMinPopulation);
+        }
+
+        if options.sampling == (Sampling::Downscale { max_dim: 0 }) {
+            return Err(Error::InvalidMaxDim);
+        }
+
+        Ok(Histogram::empty(options))
+    }
+
+    /// Creates a histogram from precomputed cell counts.
+    ///
+    /// See `Histogram::counts` for the cells layout.
+    /// `Error::InvalidHistogram` will be returned if the number of cells
+    /// doesn't match `Options::signal_bits`.
+    pub fn from_counts(counts: Vec<u64>, options: Options) -> Result<Self, Error> {
+        let mut histogram = Histogram::with_options(options)?;
+        if counts.len() != histogram.data.len() {
+            return Err(Error::InvalidHistogram);
+        }
+
+        histogram.data = counts;
+
+        for (i, _) in histogram.data.iter().enumerate().filter(|&(_, &n)| n != 0) {
+            let (r, g, b) = color_of_index(i, options.signal_bits);
+            histogram.r_min = cmp::min(histogram.r_min, r);
+            histogram.r_max = cmp::max(histogram.r_max, r);
+            histogram.g_min = cmp::min(histogram.g_min, g);
+            histogram.g_max = cmp::max(histogram.g_max, g);
+            histogram.b_min = cmp::min(histogram.b_min, b);
+            histogram.b_max = cmp::max(histogram.b_max, b);
+        }
+
+        Ok(histogram)
+    }
+
+    /// Returns counts of all histogram cells.
+    ///
+    /// When pixels are weighted, like with `Options::saturation_weight`,
+    /// a pixel adds its weight multiplied by 255, so weights can be fractional.
+    ///
+    /// Each channel is reduced to the upper `Options::signal_bits` bits,
+    /// and the cell index is `(r << (2 * signal_bits)) | (g << signal_bits) | b`.
+    pub fn counts(&self) -> &[u64] {
+        &self.data
+    }
+
+    /// Returns an average color of skipped transparent pixels.
+    ///
+    /// Returns `None` unless `Options::transparent_average` is set
+    /// or when there were no transparent pixels.
+    pub fn transparent_average(&self) -> Option<RGBA8> {
+        if !self.options.transparent_average || self.transparent == 0 {
+            return None;
+        }
+
+        let n = self.transparent;
+        let avg = |i: usize| ((self.transparent_sum[i] + n / 2) / n) as u8;
+        Some(RGBA8::new(avg(0), avg(1), avg(2), avg(3)))
+    }
+
+    /// Returns the minimum and maximum reduced r, g, b values among non-empty cells.
+    ///
+    /// This is the initial box of the median cut. `None` for an empty histogram.
+    pub fn bounds(&self) -> Option<([u8; 3], [u8; 3])> {
+        if self.r_min > self.r_max {
+            return None;
+        }
+
+        Some(([self.r_min, self.g_min, self.b_min], [self.r_max, self.g_max, self.b_max]))
+    }
+
+    /// Returns the histogram options.
+    pub fn options(&self) -> &Options {
+        &self.options
+    }
+
+    fn empty(options: Options) -> Self {
+        Histogram {
+            data: vec![0; 1 << (3 * options.signal_bits as usize)],
+            alpha: Vec::new(),
+            exact: if options.snap_exact { vec![0; 1 << (3 * options.signal_bits as usize)] } else { Vec::new() },
+            r_min: u8::MAX,
+            r_max: u8::MIN,
+            g_min: u8::MAX,
+            g_max: u8::MIN,
+            b_min: u8::MAX,
+            b_max: u8::MIN,
+            transparent: 0,
+            transparent_sum: [0; 4],
+            white: 0,
+            weighted: false,
+            options,
+        }
+    }
+
+    /// Removes all the collected pixels.
+    ///
+    /// Keeps the allocated memory.
+    pub fn clear(&mut self) {
+        for v in &mut self.data {
+            *v = 0;
+        }
+
+        for v in &mut self.alpha {
+            *v = 0;
+        }
+
+        for v in &mut self.exact {
+            *v = exact_color::NONE;
+        }
+
+        self.r_min = u8::MAX;
+        self.r_max = u8::MIN;
+        self.g_min = u8::MAX;
+        self.g_max = u8::MIN;
+        self.b_min = u8::MAX;
+        self.b_max = u8::MIN;
+        self.transparent = 0;
+        self.transparent_sum = [0; 4];
+        self.white = 0;
+        self.weighted = false;
+    }
+
+    /// Adds pixels of an image to the histogram.
+    ///
+    /// See `get_palette` for arguments description.
+    pub fn add_frame(
+        &mut self,
+        pixels: &[u8],
+        color_format: ColorFormat,
+        quality: u8,
+    ) -> Result<(), Error> {
+        check_quality(quality)?;
+
+        let image = Image::checked(pixels, color_format)?;
+        let step = quality_step(&image, quality, self.options.compat_mode);
+        self.fill(&image, step)
+    }
+
+    /// Returns a representative color palette of all the added pixels.
+    ///
+    /// See `get_palette` for arguments description.
+    pub fn quantize(&self, max_colors: u8) -> Result<Vec<Color>, Error> {
+        let mut colors = Vec::new();
+        self.quantize_into(max_colors, &mut colors)?;
+        Ok(colors)
+    }
+
+    /// Writes a representative color palette of all the added pixels into `out`.
+    ///
+    /// `out` is cleared first and stays empty on error.
+    ///
+    /// See `get_palette` for arguments description.
+    pub fn quantize_into(&self, max_colors: u8, out: &mut Vec<Color>) -> Result<(), Error> {
+        out.clear();
+        check_max_colors(max_colors)?;
+
+        let vboxes = quantize(self, max_colors)?;
+        out.extend(vboxes.iter().map(|v| v.average));
+        Ok(())
+    }
+
+    /// Returns boxes chosen by quantization, in the same order as `quantize` colors.
+    ///
+    /// Intended for debugging and visualizing palettes.
+    ///
+    /// See `get_palette` for arguments description.
+    pub fn quantize_debug(&self, max_colors: u8) -> Result<Vec<VBoxInfo>, Error> {
+        check_max_colors(max_colors)?;
+
+        let vboxes = quantize(self, max_colors)?;
+        Ok(vboxes.iter().map(|v| VBoxInfo {
+            average: v.average,
+            volume: v.volume,
+            count: v.count,
+            min: [v.r_min, v.g_min, v.b_min],
+            max: [v.r_max, v.g_max, v.b_max],
+        }).collect())
+    }
+
+    fn fill<T: Channel>(&mut self, image: &Image<T>, step: usize) -> Result<(), Error> {
+        self.options.check_image(image)?;
+
+        match self.options.sampling {
+            Sampling::Downscale { max_dim } => {
+                let thumbnail = Thumbnail::new(image, max_dim);
+                self.fill_samples(&thumbnail.image(), 1);
+            }
+            _ => self.fill_samples(image, step),
+        }
+
+        Ok(())
+    }
+
+    #[cfg(not(feature = "rayon"))]
+    fn fill_samples<T: Channel>(&mut self, image: &Image<T>, step: usize) {
+        let samples = image.pixel_count.div_ceil(step);
+        self.add_samples(image, step, 0..samples);
+    }
+
+    #[cfg(feature = "rayon")]
+    fn fill_samples<T: Channel>(&mut self, image: &Image<T>, step: usize) {
+        use rayon::prelude::*;
+
+        // Each chunk has its own histogram, so chunks should not be too small.
+        // Counts are integers, even weighted ones, so the output doesn't depend
+        // on the number of threads or on the order of merging.
+        const CHUNK_SIZE: usize = 1 << 16;
+
+        let samples = image.pixel_count.div_ceil(step);
+        let chunks = samples.div_ceil(CHUNK_SIZE);
+        let empty = || {
+            let mut histogram = Histogram::empty(self.options);
+            if !self.alpha.is_empty() {
+                histogram.alpha = vec![0; histogram.data.len()];
+            }
+            histogram
+        };
+        let histogram = (0..chunks).into_par_iter().map(|i| {
+            let start = i * CHUNK_SIZE;
+            let end = cmp::min(start + CHUNK_SIZE, samples);
+            let mut histogram = empty();
+            histogram.add_samples(image, step, start..end);
+            histogram
+        }).reduce(empty, |mut a, b| { a.merge(&b); a });
+
+        self.merge(&histogram);
+    }
+
+    /// Adds sampled pixels with indexes in the `samples` range.
+    ///
+    /// The `n`th sample is a pixel at the `n * step` position
+    /// or a random one, depending on `Options::sampling`.
+    fn add_samples<T: Channel>(
+        &mut self,
+        image: &Image<T>,
+        step: usize,
+        samples: ops::Range<usize>,
+    ) {
+        #[cfg(feature = "simd")]
+        {
+            if simd::is_supported(self) {
+                simd::add_samples(self, image, step, samples);
+                return;
+            }
+        }
+
+        for n in samples {
+            let index = match self.options.sample_index(image, step, n) {
+                Some(index) => index,
+                None => continue,
+            };
+
+            let spatial = match self.options.spatial_weight {
+                SpatialWeight::Uniform => None,
+                SpatialWeight::Center => Some(center_weight(image, index)),
+            };
+
+            let (r, g, b, a) = image.pixel(index);
+            self.add_pixel(r, g, b, a, spatial);
+        }
+    }
+
+    /// Like `fill`, but calls `progress` after each chunk of samples.
+    fn fill_with_progress<T: Channel>(
+        &mut self,
+        image: &Image<T>,
+        step: usize,
+        progress: &mut dyn FnMut(f32) -> bool,
+    ) -> Result<(), Error> {
+        self.options.check_image(image)?;
+
+        match self.options.sampling {
+            Sampling::Downscale { max_dim } => {
+                let thumbnail = Thumbnail::new(image, max_dim);
+                self.fill_samples_with_progress(&thumbnail.image(), 1, progress)
+            }
+            _ => self.fill_samples_with_progress(image, step, progress),
+        }
+    }
+
+    fn fill_samples_with_progress<T: Channel>(
+        &mut self,
+        image: &Image<T>,
+        step: usize,
+        progress: &mut dyn FnMut(f32) -> bool,
+    ) -> Result<(), Error> {
+        const CHUNK_SIZE: usize = 1 << 16;
+
+        let samples = image.pixel_count.div_ceil(step);
+        let mut start = 0;
+        while start < samples {
+            let end = cmp::min(start + CHUNK_SIZE, samples);
+            self.add_samples(image, step, start..end);
+            if !progress(end as f32 / samples as f32) {
+                return Err(Error::Cancelled);
+            }
+
+            start = end;
+        }
+
+        Ok(())
+    }
+
+    /// Adds a pixel, unless it should be skipped.
+    ///
+    /// `spatial` is an additional weight multiplier.
+    fn add_pixel(&mut self, r: u8, g: u8, b: u8, a: u8, spatial: Option<f32>) {
+        let (r, g, b, a) = match self.options.composite_over {
+            Some(bg) => (blend(r, bg.r, a), blend(g, bg.g, a), blend(b, bg.b, a), 255),
+            None => (r, g, b, a),
+        };
+
+        if self.options.is_transparent(a) {
+            self.add_transparent(r, g, b, a);
+            return;
+        }
+
+        if self.options.is_white(r, g, b) {
+            self.white += 1;
+            return;
+        }
+
+        if self.options.skips(r, g, b, a) {
+            return;
+        }
+
+        // Nearly transparent pixels can have a zero weight.
+        let count = self.weight(r, g, b, a, spatial);
+        if count == 0 {
+            self.add_transparent(r, g, b, a);
+            return;
+        }
+
+        let color = Color::new(r, g, b);
+        let (r, g, b) = match self.options.color_space {
+            ColorSpace::Rgb => (r, g, b),
+            ColorSpace::Lab => encode_lab(color),
+        };
+
+        self.add(r, g, b, a, count);
+
+        if !self.exact.is_empty() {
+            let shift = 8 - self.options.signal_bits;
+            let index = make_color_index_of(r >> shift, g >> shift, b >> shift, self.options.signal_bits);
+            self.exact[index] = exact_color::merge(self.exact[index], exact_color::from(color));
+        }
+    }
+
+    fn add_transparent(&mut self, r: u8, g: u8, b: u8, a: u8) {
+        self.transparent += 1;
+        if self.options.transparent_average {
+            self.transparent_sum[0] += r as u64;
+            self.transparent_sum[1] += g as u64;
+            self.transparent_sum[2] += b as u64;
+            self.transparent_sum[3] += a as u64;
+        }
+    }
+
+    /// Returns a number of histogram samples of a pixel.
+    ///
+    /// Weighted pixels are counted in `1 / WEIGHT_SCALE` units.
+    fn weight(&mut self, r: u8, g: u8, b: u8, a: u8, spatial: Option<f32>) -> u64 {
+        let saturation_weight = self.options.saturation_weight;
+        let alpha_weighted = self.options.alpha_mode == AlphaMode::Weighted;
+        if saturation_weight == 0.0 && spatial.is_none() && !alpha_weighted {
+            return 1;
+        }
+
+        self.weighted = true;
+
+        let max = cmp::max(cmp::max(r, g), b);
+        let min = cmp::min(cmp::min(r, g), b);
+        let saturation = if max == 0 { 0.0 } else { (max - min) as f32 / max as f32 };
+        let opacity = if alpha_weighted { a as f32 / 255.0 } else { 1.0 };
+        let weight = (1.0 + saturation_weight * saturation) * spatial.unwrap_or(1.0) * opacity;
+        (WEIGHT_SCALE as f32 * weight + 0.5) as u64
+    }
+
+    /// Converts a count of cells into a number of pixels.
+    fn pixel_count(&self, count: u64) -> u64 {
+        if self.weighted {
+            count.saturating_add(WEIGHT_SCALE / 2) / WEIGHT_SCALE
+        } else {
+            count
+        }
+    }
+
+    fn add(&mut self, r: u8, g: u8, b: u8, a: u8, count: u64) {
+        let shift = 8 - self.options.signal_bits;
+        let shifted_r = r >> shift;
+        let shifted_b = b >> shift;
+        let shifted_g = g >> shift;
+
+        self.r_min = cmp::min(self.r_min, shifted_r);
+        self.r_max = cmp::max(self.r_max, shifted_r);
+        self.g_min = cmp::min(self.g_min, shifted_g);
+        self.g_max = cmp::max(self.g_max, shifted_g);
+        self.b_min = cmp::min(self.b_min, shifted_b);
+        self.b_max = cmp::max(self.b_max, shifted_b);
+
+        // Increment histogram.
+        let index = make_color_index_of(shifted_r, shifted_g, shifted_b, self.options.signal_bits);
+        self.data[index] = self.data[index].saturating_add(count);
+        if !self.alpha.is_empty() {
+            self.alpha[index] = self.alpha[index].saturating_add((a as u64).saturating_mul(count));
+        }
+    }
+
+    #[inline]
+    fn get(&self, r: u8, g: u8, b: u8) -> u64 {
+        self.data[make_color_index_of(r, g, b, self.options.signal_bits)]
+    }
+
+    /// Returns a sum of alpha values of a cell or `None` when alpha is not tracked.
+    #[inline]
+    fn get_alpha(&self, r: u8, g: u8, b: u8) -> Option<u64> {
+        self.alpha.get(make_color_index_of(r, g, b, self.options.signal_bits)).cloned()
+    }
+
+    /// Returns a number of possible values per channel.
+    fn vbox_length(&self) -> usize {
+        1 << self.options.signal_bits
+    }
+
+    /// Returns a size of a histogram cell per channel.
+    fn multiplier(&self) -> i32 {
+        1 << (8 - self.options.signal_bits)
+    }
+
+    #[cfg(feature = "rayon")]
+    fn merge(&mut self, other: &Histogram) {
+        for (a, b) in self.data.iter_mut().zip(other.data.iter()) {
+            *a = a.saturating_add(*b);
+        }
+
+        for (a, b) in self.alpha.iter_mut().zip(other.alpha.iter()) {
+            *a = a.saturating_add(*b);
+        }
+
+        for (a, b) in self.exact.iter_mut().zip(other.exact.iter()) {
+            *a = exact_color::merge(*a, *b);
+        }
+
+        self.transparent += other.transparent;
+        for (a, b) in self.transparent_sum.iter_mut().zip(other.transparent_sum.iter()) {
+            *a += *b;
+        }
+        self.white += other.white;
+        self.weighted |= other.weighted;
+
+        self.r_min = cmp::min(self.r_min, other.r_min);
+        self.r_max = cmp::max(self.r_max, other.r_max);
+        self.g_min = cmp::min(self.g_min, other.g_min);
+        self.g_max = cmp::max(self.g_max, other.g_max);
+        self.b_min = cmp::min(self.b_min, other.b_min);
+        self.b_max = cmp::max(self.b_max, other.b_max);
+    }
+
+    /// Returns the beginning vbox containing all the pixels.
+    /// Returns a number of non-empty cells.
+    fn cell_count(&self) -> usize {
+        self.data.iter().filter(|&&count| count != 0).count()
+    }
+
+    fn vbox(&self) -> VBox {
+        let mut vbox = VBox::new(
+            self.r_min, self.r_max,
+            self.g_min, self.g_max,
+            self.b_min, self.b_max,
+        );
+        vbox.recalc(self);
+        vbox
+    }
+}
+
+/// An incremental histogram builder for images split into chunks.
+///
+/// Chunks can be split at any byte, a partial trailing pixel is kept until the next chunk.
+/// Pixels are sampled with a stride, just like in `get_palette`,
+/// so `Options::sampling` and `Options::spatial_weight` are ignored.
+///
+/// # Example
+///
+/// ```no_run
+/// # let chunks: Vec<Vec<u8>> = Vec::new();
+/// use color_thief::{ColorFormat, HistogramBuilder};
+///
+/// let mut builder = HistogramBuilder::new().quality(5);
+/// for chunk in &chunks {
+///     builder.push_pixels(chunk, ColorFormat::Rgb).unwrap();
+/// }
+///
+/// let colors = builder.finish().quantize(10).unwrap();
+/// ```
+#[derive(Clone,Debug)]
+pub struct HistogramBuilder {
+    histogram: Histogram,
+    quality: u8,
+    /// Bytes of a partial pixel from the end of the previous chunk.
+    pending: Vec<u8>,
+    /// A number of pixels pushed so far.
+    position: usize,
+    /// A color format of the first chunk.
+    color_format: Option<ColorFormat>,
 }
 
-impl VBox {
-    fn new(
-        r_min: u8, r_max: u8,
-        g_min: u8, g_max: u8,
-        b_min: u8, b_max: u8,
-    ) -> VBox {
-        VBox {
-            r_min: r_min,
-            r_max: r_max,
-            g_min: g_min,
-            g_max: g_max,
-            b_min: b_min,
-            b_max: b_max,
-            average: Color::new(0, 0, 0),
-            volume: 0,
-            count: 0,
+impl Default for HistogramBuilder {
+    fn default() -> Self {
+        HistogramBuilder {
+            histogram: Histogram::new(),
+            quality: 10,
+            pending: Vec::new(),
+            position: 0,
+            color_format: None,
         }
+    }
+}
 
-        // `recalc()` should be called right after `new()`.
+impl HistogramBuilder {
+    /// Creates a new builder with default options.
+    ///
+    /// Quality is 10.
+    pub fn new() -> Self {
+        HistogramBuilder::default()
     }
 
-    fn recalc(&mut self, histogram: &[i32]) {
-        self.average = self.calc_average(histogram);
-        self.count = self.calc_count(histogram);
-        self.volume = self.calc_volume();
+    /// Creates a new builder with the specified options.
+    pub fn with_options(options: Options) -> Result<Self, Error> {
+        Ok(HistogramBuilder {
+            histogram: Histogram::with_options(options)?,
+            ..HistogramBuilder::default()
+        })
     }
 
-    /// Get 3 dimensional volume of the color space.
-    fn calc_volume(&self) -> i32 {
-          (self.r_max as i32 - self.r_min as i32 + 1)
-        * (self.g_max as i32 - self.g_min as i32 + 1)
-        * (self.b_max as i32 - self.b_min as i32 + 1)
+    /// Sets quality of an output colors.
+    ///
+    /// See `get_palette` for details.
+    /// Since the image size is unknown, 0 samples every pixel.
+    pub fn quality(mut self, quality: u8) -> Self {
+        self.quality = quality;
+        self
     }
 
-    /// Get total count of histogram samples.
-    fn calc_count(&self, histogram: &[i32]) -> i32 {
-        let mut count = 0;
-        for i in self.r_min..(self.r_max + 1) {
-            for j in self.g_min..(self.g_max + 1) {
-                for k in self.b_min..(self.b_max + 1) {
-                    let index = make_color_index_of(i, j, k);
-                    count += histogram[index];
-                }
-            }
+    /// Adds the next chunk of an image.
+    ///
+    /// The color format must be the same for all chunks.
+    /// `Error::ColorFormatMismatch` otherwise.
+    ///
+    /// See `get_palette` for arguments description.
+    pub fn push_pixels(&mut self, pixels: &[u8], color_format: ColorFormat) -> Result<(), Error> {
+        check_quality(self.quality)?;
+
+        if self.color_format.is_some_and(|f| f != color_format) {
+            return Err(Error::ColorFormatMismatch);
         }
 
-        count
-    }
-
-    fn calc_average(&self, histogram: &[i32]) -> Color {
-        let mut ntot = 0;
+        let channels = color_format.channels();
+        let total = self.position + (self.pending.len() + pixels.len()) / channels;
+        self.histogram.options.check_size(total)?;
 
-        let mut r_sum = 0;
-        let mut g_sum = 0;
-        let mut b_sum = 0;
+        self.color_format = Some(color_format);
 
-        for i in self.r_min..(self.r_max + 1) {
-            for j in self.g_min..(self.g_max + 1) {
-                for k in self.b_min..(self.b_max + 1) {
-                    let index = make_color_index_of(i, j, k);
-                    let hval = histogram[index] as f64;
-                    ntot += hval as i32;
-                    r_sum += (hval * (i as f64 + 0.5) * MULTIPLIER_64) as i32;
-                    g_sum += (hval * (j as f64 + 0.5) * MULTIPLIER_64) as i32;
-                    b_sum += (hval * (k as f64 + 0.5) * MULTIPLIER_64) as i32;
-                }
+        let mut pixels = pixels;
+        if !self.pending.is_empty() {
+            let len = cmp::min(channels - self.pending.len(), pixels.len());
+            self.pending.extend_from_slice(&pixels[..len]);
+            pixels = &pixels[len..];
+            if self.pending.len() < channels {
+                return Ok(());
             }
+
+            // A pixel has at most 4 channels.
+            let mut pixel = [0; 4];
+            pixel[..channels].copy_from_slice(&self.pending);
+            self.pending.clear();
+            self.add_pixels(&pixel[..channels], color_format);
         }
 
-        if ntot > 0 {
-            let r = r_sum / ntot;
-            let g = g_sum / ntot;
-            let b = b_sum / ntot;
-            Color::new(r as u8, g as u8, b as u8)
+        let len = pixels.len() - pixels.len() % channels;
+        self.add_pixels(&pixels[..len], color_format);
+        self.pending.extend_from_slice(&pixels[len..]);
+        Ok(())
+    }
+
+    /// Adds whole pixels, continuing the stride of previous chunks.
+    fn add_pixels(&mut self, pixels: &[u8], color_format: ColorFormat) {
+        let image = Image::packed(pixels, color_format);
+        let step = if self.quality == 0 {
+            1
         } else {
-            let r = MULTIPLIER * (self.r_min as i32 + self.r_max as i32 + 1) / 2;
-            let g = MULTIPLIER * (self.g_min as i32 + self.g_max as i32 + 1) / 2;
-            let b = MULTIPLIER * (self.b_min as i32 + self.b_max as i32 + 1) / 2;
-            Color::new(cmp::min(r, 255) as u8,
-                       cmp::min(g, 255) as u8,
-                       cmp::min(b, 255) as u8)
+            quality_step(&image, self.quality, self.histogram.options.compat_mode)
+        };
+        let first = (step - self.position % step) % step;
+        for index in (first..image.pixel_count).step_by(step) {
+            let (r, g, b, a) = image.pixel(index);
+            self.histogram.add_pixel(r, g, b, a, None);
         }
+
+        self.position += image.pixel_count;
     }
 
-    fn widest_color_channel(&self) -> ColorChannel {
-        let r_width = self.r_max - self.r_min;
-        let g_width = self.g_max - self.g_min;
-        let b_width = self.b_max - self.b_min;
+    /// Returns the histogram of all the pushed pixels.
+    ///
+    /// A partial trailing pixel is discarded.
+    pub fn finish(self) -> Histogram {
+        self.histogram
+    }
+}
 
-        let max = cmp::max(cmp::max(r_width, g_width), b_width);
+/// Checks that the quality is in a 0..10 range.
+fn check_quality(quality: u8) -> Result<(), Error> {
+    if quality > 10 {
+        return Err(Error::InvalidQuality);
+    }
 
-        if max == r_width {
-            ColorChannel::Red
-        } else if max == g_width {
-            ColorChannel::Green
-        } else {
-            ColorChannel::Blue
-        }
+    Ok(())
+}
+
+/// Checks that at least 2 colors are requested.
+fn check_max_colors(max_colors: u8) -> Result<(), Error> {
+    if max_colors < 2 {
+        return Err(Error::InvalidMaxColors);
     }
+
+    Ok(())
 }
 
-fn make_histogram_and_vbox(
-    pixels: &[u8],
-    color_format: ColorFormat,
-    step: u8,
-) -> (VBox, Vec<i32>) {
-    let mut histogram: Vec<i32> = (0..HISTOGRAM_SIZE).map(|_| 0).collect();
-
-    let mut r_min = u8::MAX;
-    let mut r_max = u8::MIN;
-    let mut g_min = u8::MAX;
-    let mut g_max = u8::MIN;
-    let mut b_min = u8::MAX;
-    let mut b_max = u8::MIN;
-
-    let colors_count = match color_format {
-        ColorFormat::Rgb => 3,
-        ColorFormat::Rgba => 4,
-        ColorFormat::Argb => 4,
-        ColorFormat::Bgr => 3,
-        ColorFormat::Bgra => 4,
-    };
+/// Checks the `quality` and `max_colors` arguments of palette functions.
+fn check_palette_args(quality: u8, max_colors: u8) -> Result<(), Error> {
+    check_quality(quality)?;
+    check_max_colors(max_colors)
+}
 
-    let pixel_count = pixels.len() / colors_count;
-    let mut i = 0;
-    while i < pixel_count {
-        let pos = i * colors_count;
+/// Returns sampled pixels that are not skipped by options.
+fn sampled_pixels<'a, T: Channel + 'a>(
+    image: &'a Image<T>,
+    step: usize,
+    options: &'a Options,
+) -> impl Iterator<Item = (u8, u8, u8, u8)> + 'a {
+    (0..image.pixel_count.div_ceil(step))
+        .filter_map(move |n| options.sample_index(image, step, n))
+        .map(move |index| image.pixel(index))
+        .filter(move |&(r, g, b, a)| !options.skips(r, g, b, a))
+}
 
-        let (r, g, b, a) = color_parts(pixels, color_format, pos);
+/// Returns a sampling step in pixels for the specified quality.
+fn quality_step<T: Channel>(image: &Image<T>, quality: u8, mode: CompatMode) -> usize {
+    if quality == 0 {
+        return auto_step(image.pixel_count);
+    }
 
-        i += colors_count * step as usize;
+    match mode {
+        // Historically, the step is multiplied by the number of channels.
+        CompatMode::Default => image.color_format.channels() * quality as usize,
+        CompatMode::ColorThiefPy => quality as usize,
+    }
+}
 
-        // If pixel is mostly opaque or white.
-        if a < 125 || (r > 250 && g > 250 && b > 250) {
-            continue;
-        }
+/// Returns a sampling step in pixels to sample about `AUTO_SAMPLES` pixels.
+fn auto_step(pixel_count: usize) -> usize {
+    cmp::max(pixel_count / AUTO_SAMPLES, 1)
+}
 
-        let shifted_r = r >> RIGHT_SHIFT as u8;
-        let shifted_b = b >> RIGHT_SHIFT as u8;
-        let shifted_g = g >> RIGHT_SHIFT as u8;
+/// Returns a sampling step in pixels for the specified fraction of pixels.
+fn fraction_step<T: Channel>(image: &Image<T>, fraction: f32) -> usize {
+    let samples = ceil(image.pixel_count as f64 * fraction as f64) as usize;
+    cmp::max(image.pixel_count / cmp::max(samples, 1), 1)
+}
 
-        r_min = cmp::min(r_min, shifted_r);
-        r_max = cmp::max(r_max, shifted_r);
-        g_min = cmp::min(g_min, shifted_g);
-        g_max = cmp::max(g_max, shifted_g);
-        b_min = cmp::min(b_min, shifted_b);
-        b_max = cmp::max(b_max, shifted_b);
+/// Returns a position weight of a pixel, from 1 in the corners to 4 in the center.
+fn center_weight<T: Channel>(image: &Image<T>, index: usize) -> f32 {
+    let height = image.pixel_count.div_ceil(image.width);
+    let dx = ((index % image.width) as f32 + 0.5) / image.width as f32 - 0.5;
+    let dy = ((index / image.width) as f32 + 0.5) / height as f32 - 0.5;
 
-        // Increment histogram.
-        let index = make_color_index_of(shifted_r, shifted_g, shifted_b);
-        histogram[index] += 1;
-    }
+    // A squared distance to the center, where corners are at 1.
+    let distance = (dx * dx + dy * dy) * 2.0;
+    1.0 + 3.0 * (1.0 - distance)
+}
 
-    let mut vbox = VBox::new(r_min, r_max, g_min, g_max, b_min, b_max);
-    vbox.recalc(&histogram);
+/// Returns the `n`th number of the SplitMix64 sequence.
+///
+/// Doesn't require a state, so samples can be processed in any order.
+fn splitmix64(seed: u64, n: u64) -> u64 {
+    let mut z = seed.wrapping_add(n.wrapping_add(1).wrapping_mul(0x9e3779b97f4a7c15));
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
 
-    (vbox, histogram)
+/// Converts a color into Lab with each component mapped into a 0..255 range.
+fn encode_lab(c: Color) -> (u8, u8, u8) {
+    let (l, a, b) = color::to_lab(&c);
+    let clamp = |v: f32| (v + 0.5).clamp(0.0, 255.0) as u8;
+    (clamp(l * 2.55), clamp(a + 128.0), clamp(b + 128.0))
 }
 
+/// The inverse of `encode_lab`.
+fn decode_lab(c: Color) -> Color {
+    color::from_lab(c.r as f32 / 2.55, c.g as f32 - 128.0, c.b as f32 - 128.0)
+}
 
 /// Extracts r, g, b, a color parts.
-fn color_parts(
-    pixels: &[u8],
+fn color_parts<T: Channel>(
+    pixels: &[T],
     color_format: ColorFormat,
     pos: usize,
 ) -> (u8, u8, u8, u8) {
     match color_format {
         ColorFormat::Rgb => {
-            (pixels[pos + 0],
-             pixels[pos + 1],
-             pixels[pos + 2],
+            (pixels[pos].to_u8(),
+             pixels[pos + 1].to_u8(),
+             pixels[pos + 2].to_u8(),
              255)
         }
         ColorFormat::Rgba => {
-            (pixels[pos + 0],
-             pixels[pos + 1],
-             pixels[pos + 2],
-             pixels[pos + 3])
+            (pixels[pos].to_u8(),
+             pixels[pos + 1].to_u8(),
+             pixels[pos + 2].to_u8(),
+             pixels[pos + 3].to_u8())
         }
         ColorFormat::Argb => {
-            (pixels[pos + 1],
-             pixels[pos + 2],
-             pixels[pos + 3],
-             pixels[pos + 0])
+            (pixels[pos + 1].to_u8(),
+             pixels[pos + 2].to_u8(),
+             pixels[pos + 3].to_u8(),
+             pixels[pos].to_u8())
         },
+        ColorFormat::Abgr => {
+            (pixels[pos + 3].to_u8(),
+             pixels[pos + 2].to_u8(),
+             pixels[pos + 1].to_u8(),
+             pixels[pos].to_u8())
+        }
         ColorFormat::Bgr => {
-            (pixels[pos + 2],
-             pixels[pos + 1],
-             pixels[pos + 0],
+            (pixels[pos + 2].to_u8(),
+             pixels[pos + 1].to_u8(),
+             pixels[pos].to_u8(),
              255)
         }
         ColorFormat::Bgra => {
-            (pixels[pos + 2],
-             pixels[pos + 1],
-             pixels[pos + 0],
-             pixels[pos + 3])
+            (pixels[pos + 2].to_u8(),
+             pixels[pos + 1].to_u8(),
+             pixels[pos].to_u8(),
+             pixels[pos + 3].to_u8())
+        }
+        ColorFormat::Rgbx => {
+            (pixels[pos].to_u8(),
+             pixels[pos + 1].to_u8(),
+             pixels[pos + 2].to_u8(),
+             255)
+        }
+        ColorFormat::Bgrx => {
+            (pixels[pos + 2].to_u8(),
+             pixels[pos + 1].to_u8(),
+             pixels[pos].to_u8(),
+             255)
+        }
+        ColorFormat::Gray => {
+            (pixels[pos].to_u8(),
+             pixels[pos].to_u8(),
+             pixels[pos].to_u8(),
+             255)
+        }
+        ColorFormat::GrayAlpha => {
+            (pixels[pos].to_u8(),
+             pixels[pos].to_u8(),
+             pixels[pos].to_u8(),
+             pixels[pos + 1].to_u8())
+        }
+        ColorFormat::RgbaPremultiplied => {
+            let a = pixels[pos + 3].to_u8();
+            (unpremultiply(pixels[pos].to_u8(), a),
+             unpremultiply(pixels[pos + 1].to_u8(), a),
+             unpremultiply(pixels[pos + 2].to_u8(), a),
+             a)
+        }
+        ColorFormat::Cmyk => {
+            let k = pixels[pos + 3].to_u8();
+            (cmyk_to_rgb(pixels[pos].to_u8(), k),
+             cmyk_to_rgb(pixels[pos + 1].to_u8(), k),
+             cmyk_to_rgb(pixels[pos + 2].to_u8(), k),
+             255)
+        }
+        ColorFormat::YCbCr => {
+            let (r, g, b) = ycbcr_to_rgb(pixels[pos].to_u8(),
+                                         pixels[pos + 1].to_u8(),
+                                         pixels[pos + 2].to_u8());
+            (r, g, b, 255)
         }
     }
 }
 
+/// Converts a full range BT.601 YCbCr into RGB.
+///
+/// Uses 16-bit fixed-point coefficients, just like libjpeg.
+fn ycbcr_to_rgb(y: u8, cb: u8, cr: u8) -> (u8, u8, u8) {
+    let y = (y as i32) << 16;
+    let cb = cb as i32 - 128;
+    let cr = cr as i32 - 128;
+    let clamp = |v: i32| ((v + 32768) >> 16).clamp(0, 255) as u8;
+    (clamp(y + 91881 * cr),
+     clamp(y - 22554 * cb - 46802 * cr),
+     clamp(y + 116130 * cb))
+}
+
+fn cmyk_to_rgb(c: u8, k: u8) -> u8 {
+    (((255 - c) as u32 * (255 - k) as u32 + 127) / 255) as u8
+}
+
+/// Blends a color channel over a background one.
+fn blend(c: u8, background: u8, a: u8) -> u8 {
+    ((c as u32 * a as u32 + background as u32 * (255 - a) as u32 + 127) / 255) as u8
+}
+
+fn unpremultiply(c: u8, a: u8) -> u8 {
+    if a == 0 {
+        0
+    } else {
+        cmp::min(c as u32 * 255 / a as u32, 255) as u8
+    }
+}
+
+/// `partial_sum` and `look_ahead_sum` are reusable buffers of `Histogram::vbox_length` size.
 fn apply_median_cut(
-    histogram: &[i32],
-    vbox: &mut VBox,
+    histogram: &Histogram,
+    vbox: VBox,
+    partial_sum: &mut [u64],
+    look_ahead_sum: &mut [u64],
 ) -> Result<(VBox, Option<VBox>), Error> {
     if vbox.count == 0 {
         return Err(Error::InvalidVBox);
@@ -319,22 +3345,21 @@ fn apply_median_cut(
 
     // Only one pixel, no split.
     if vbox.count == 1 {
-        return Ok((vbox.clone(), None));
+        return Ok((vbox, None));
     }
 
     // Find the partial sum arrays along the selected axis.
     let mut total = 0;
-    let mut partial_sum: Vec<i32> = (0..VBOX_LENGTH).map(|_| -1).collect();
+    partial_sum.fill(0);
 
     let axis = vbox.widest_color_channel();
     match axis {
         ColorChannel::Red => {
-            for i in vbox.r_min..(vbox.r_max + 1) {
+            for i in vbox.r_min..=vbox.r_max {
                 let mut sum = 0;
-                for j in vbox.g_min..(vbox.g_max + 1) {
-                    for k in vbox.b_min..(vbox.b_max + 1) {
-                        let index = make_color_index_of(i, j, k);
-                        sum += histogram[index];
+                for j in vbox.g_min..=vbox.g_max {
+                    for k in vbox.b_min..=vbox.b_max {
+                        sum += histogram.get(i, j, k);
                     }
                 }
                 total += sum;
@@ -342,12 +3367,11 @@ fn apply_median_cut(
             }
         }
         ColorChannel::Green => {
-            for i in vbox.g_min..(vbox.g_max + 1) {
+            for i in vbox.g_min..=vbox.g_max {
                 let mut sum = 0;
-                for j in vbox.r_min..(vbox.r_max + 1) {
-                    for k in vbox.b_min..(vbox.b_max + 1) {
-                        let index = make_color_index_of(j, i, k);
-                        sum += histogram[index];
+                for j in vbox.r_min..=vbox.r_max {
+                    for k in vbox.b_min..=vbox.b_max {
+                        sum += histogram.get(j, i, k);
                     }
                 }
                 total += sum;
@@ -355,12 +3379,11 @@ fn apply_median_cut(
             }
         }
         ColorChannel::Blue => {
-            for i in vbox.b_min..(vbox.b_max + 1) {
+            for i in vbox.b_min..=vbox.b_max {
                 let mut sum = 0;
-                for j in vbox.r_min..(vbox.r_max + 1) {
-                    for k in vbox.g_min..(vbox.g_max + 1) {
-                        let index = make_color_index_of(j, k, i);
-                        sum += histogram[index];
+                for j in vbox.r_min..=vbox.r_max {
+                    for k in vbox.g_min..=vbox.g_max {
+                        sum += histogram.get(j, k, i);
                     }
                 }
                 total += sum;
@@ -369,21 +3392,21 @@ fn apply_median_cut(
         }
     }
 
-    let mut look_ahead_sum: Vec<i32> = (0..VBOX_LENGTH).map(|_| -1).collect();
-    for (i, sum) in partial_sum.iter().enumerate().filter(|&(_, sum)| *sum != -1) {
+    look_ahead_sum.fill(0);
+    for (i, sum) in partial_sum.iter().enumerate().filter(|&(_, sum)| *sum != 0) {
         look_ahead_sum[i] = total - sum;
     }
 
-    cut(axis, vbox, histogram, &partial_sum, &look_ahead_sum, total)
+    cut(axis, &vbox, histogram, partial_sum, look_ahead_sum, total)
 }
 
 fn cut(
     axis: ColorChannel,
     vbox: &VBox,
-    histogram: &[i32],
-    partial_sum: &[i32],
-    look_ahead_sum: &[i32],
-    total: i32,
+    histogram: &Histogram,
+    partial_sum: &[u64],
+    look_ahead_sum: &[u64],
+    total: u64,
 ) -> Result<(VBox, Option<VBox>), Error> {
     let (vbox_min, vbox_max) = match axis {
         ColorChannel::Red =>   (vbox.r_min as i32, vbox.r_max as i32),
@@ -402,20 +3425,28 @@ fn cut(
         let left = i - vbox_min;
         let right = vbox_max - i;
 
-        let mut d2 = if left <= right {
-            cmp::min(vbox_max - 1, i + right / 2)
-        } else {
-            // 2.0 and cast to int is necessary to have the same
-            // behavior as in JavaScript.
-            cmp::max(vbox_min, ((i - 1) as f64 - left as f64 / 2.0) as i32)
+        let mut d2 = match histogram.options.rounding {
+            RoundingMode::Truncate => if left <= right {
+                cmp::min(vbox_max - 1, i + right / 2)
+            } else {
+                // 2.0 and cast to int is necessary to have the same
+                // behavior as in JavaScript.
+                cmp::max(vbox_min, ((i - 1) as f64 - left as f64 / 2.0) as i32)
+            },
+            RoundingMode::Nearest => if left <= right {
+                cmp::min(vbox_max - 1, i + (right + 1) / 2)
+            } else {
+                // `i - 1 - left / 2`, rounded half up.
+                cmp::max(vbox_min, (2 * (i - 1) - left + 1).div_euclid(2))
+            },
         };
 
         // Avoid 0-count.
-        while d2 < 0 || partial_sum[d2 as usize] <= 0 {
+        while d2 < 0 || partial_sum[d2 as usize] == 0 {
             d2 += 1;
         }
         let mut count2 = look_ahead_sum[d2 as usize];
-        while count2 == 0 && d2 > 0 && partial_sum[d2 as usize - 1] > 0 {
+        while count2 == 0 && d2 > 0 && partial_sum[d2 as usize - 1] != 0 {
             d2 -= 1;
             count2 = look_ahead_sum[d2 as usize];
         }
@@ -446,98 +3477,488 @@ fn cut(
 }
 
 fn quantize(
-    pixels: &[u8],
-    color_format: ColorFormat,
-    quality: u8,
+    histogram: &Histogram,
     max_colors: u8,
-) -> Result<Vec<Color>, Error> {
-    // Get the histogram and the beginning vbox from the colors.
-    let (vbox, histogram) = make_histogram_and_vbox(pixels, color_format, quality);
+) -> Result<Vec<VBox>, Error> {
+    // Get the beginning vbox from the colors.
+    let vbox = histogram.vbox();
+
+    // All pixels were skipped.
+    if vbox.count == 0 {
+        if histogram.white != 0 {
+            if !histogram.options.white_fallback {
+                return Err(Error::OnlyWhite);
+            }
+
+            let max = u8::MAX >> (8 - histogram.options.signal_bits);
+            let mut white = VBox::new(max, max, max, max, max, max);
+            white.average = Color::new(255, 255, 255);
+            white.count = histogram.white;
+            white.volume = 1;
+            return Ok(vec![white]);
+        }
+
+        if histogram.transparent != 0 {
+            return Err(Error::NoVisiblePixels);
+        }
+
+        return Err(Error::EmptyImage);
+    }
+
+    let total = vbox.count;
+
+    if histogram.options.exact_colors {
+        let found = histogram.cell_count();
+        if found < max_colors as usize {
+            return Err(Error::InsufficientColors { found: found as u8, requested: max_colors });
+        }
+    }
+
+    let mut vboxes = match histogram.options.algorithm {
+        Algorithm::MedianCut => median_cut(vbox, histogram, max_colors)?,
+        Algorithm::Octree => {
+            let vboxes = octree::quantize(histogram, max_colors);
+            merge_close_colors(vboxes, histogram.options.min_distance)
+        }
+    };
+
+    refine(&mut vboxes, histogram);
+    filter_by_population(&mut vboxes, total, histogram.options.min_population);
+
+    if histogram.options.color_space == ColorSpace::Lab {
+        for vbox in &mut vboxes {
+            vbox.average = decode_lab(vbox.average);
+        }
+    }
+
+    if !histogram.exact.is_empty() {
+        snap_exact(&mut vboxes, histogram);
+    }
+
+    sort_vboxes(&mut vboxes, histogram.options.sort_order, histogram.options.compat_mode);
+
+    for vbox in &mut vboxes {
+        vbox.count = histogram.pixel_count(vbox.count);
+    }
+
+    Ok(vboxes)
+}
 
-    // Priority queue.
-    let mut pq = vec![vbox.clone()];
+fn median_cut(
+    vbox: VBox,
+    histogram: &Histogram,
+    max_colors: u8,
+) -> Result<Vec<VBox>, Error> {
+    let mode = histogram.options.compat_mode;
+    let (by_count, by_product): (Comparator, Comparator) = match mode {
+        CompatMode::Default => (compare_by_count, compare_by_product),
+        CompatMode::ColorThiefPy => (compare_by_count_py, compare_by_product_py),
+    };
 
     // Round up to have the same behavior as in JavaScript
-    let target = (FRACTION_BY_POPULATION * max_colors as f64).ceil() as u8;
+    let target = ceil(histogram.options.population_fraction as f64 * max_colors as f64) as u8;
 
     // First set of colors, sorted by population.
-    iterate(&mut pq, compare_by_count, target, &histogram)?;
+    let mut pq = PriorityQueue::new(by_count);
+    pq.push(vbox);
+    iterate(&mut pq, target, histogram)?;
+
+    let mut vboxes = pq.into_sorted_vec();
+
+    // colorthief-py moves boxes to the second queue one by one,
+    // starting from the most populated one.
+    if mode == CompatMode::ColorThiefPy {
+        vboxes.reverse();
+    }
 
     // Re-sort by the product of pixel occupancy times the size in color space.
-    pq.sort_by(compare_by_product);
+    let mut pq = PriorityQueue::new(by_product);
+    for vbox in vboxes {
+        pq.push(vbox);
+    }
 
     // next set - generate the median cuts using the (npix * vol) sorting.
     let len = pq.len() as u8;
-    iterate(&mut pq, compare_by_product, max_colors - len, &histogram)?;
+    iterate(&mut pq, max_colors - len, histogram)?;
+
+    let min_distance = histogram.options.min_distance;
+    let size = cmp::min(pq.len(), max_colors as usize);
+    let mut vboxes = queue_palette(&pq, size, min_distance);
+    while histogram.options.refill && vboxes.len() < size {
+        // Cut as many boxes as were merged.
+        let len = pq.len();
+        iterate(&mut pq, (size - vboxes.len()) as u8 + 1, histogram)?;
+        if pq.len() == len {
+            break;
+        }
+
+        vboxes = queue_palette(&pq, size, min_distance);
+    }
 
+    Ok(vboxes)
+}
+
+/// Returns at most `size` boxes from the queue with close colors merged.
+fn queue_palette(queue: &PriorityQueue, size: usize, min_distance: u8) -> Vec<VBox> {
     // Reverse to put the highest elements first into the color map.
-    pq.reverse();
+    let mut vboxes = queue.to_sorted_vec();
+    vboxes.reverse();
 
-    // Keep at most `max_colors` in the resulting vector.
-    let mut colors: Vec<Color> = pq.iter().map(|v| v.average).collect();
-    colors.truncate(max_colors as usize);
+    if min_distance != 0 {
+        vboxes = merge_close_colors(vboxes, min_distance);
+    }
 
-    Ok(colors)
+    vboxes.truncate(size);
+    vboxes
 }
 
-// Inner function to do the iteration.
-fn iterate<P>(
-    queue: &mut Vec<VBox>,
-    comparator: P,
-    target: u8,
-    histogram: &[i32],
-) -> Result<(), Error>
-    where P: FnMut(&VBox, &VBox) -> cmp::Ordering + Copy
-{
-    let mut color = 1;
+/// Replaces box colors with the closest exact pixel colors of the box cells.
+///
+/// See `Options::snap_exact`.
+fn snap_exact(vboxes: &mut [VBox], histogram: &Histogram) {
+    let max_distance = histogram.multiplier() as u32;
+    let bits = histogram.options.signal_bits;
+    for vbox in vboxes {
+        let mut best = (max_distance * max_distance + 1, vbox.average);
+        for i in vbox.r_min..=vbox.r_max {
+            for j in vbox.g_min..=vbox.g_max {
+                for k in vbox.b_min..=vbox.b_max {
+                    let exact = histogram.exact[make_color_index_of(i, j, k, bits)];
+                    if let Some(color) = exact_color::to_color(exact) {
+                        let distance = distance_sq(color, vbox.average);
+                        if distance < best.0 {
+                            best = (distance, color);
+                        }
+                    }
+                }
+            }
+        }
 
-    for _ in 0..MAX_ITERATIONS {
-        if let Some(mut vbox) = queue.last().cloned() {
-            if vbox.count == 0 {
-                queue.sort_by(comparator);
-                continue;
+        vbox.average = best.1;
+    }
+}
+
+/// Removes boxes with less than `min_population` of `total` pixels,
+/// but keeps the most populated one.
+fn filter_by_population(vboxes: &mut Vec<VBox>, total: u64, min_population: f32) {
+    if min_population == 0.0 {
+        return;
+    }
+
+    let min_count = min_population as f64 * total as f64;
+    if vboxes.iter().all(|v| (v.count as f64) < min_count) {
+        let most_populated = vboxes.iter().enumerate().max_by_key(|&(i, v)| (v.count, cmp::Reverse(i)));
+        if let Some((i, _)) = most_populated {
+            vboxes.swap(0, i);
+            vboxes.truncate(1);
+        }
+
+        return;
+    }
+
+    vboxes.retain(|v| v.count as f64 >= min_count);
+}
+
+/// Merges each box into the first preceding box with a close color.
+fn merge_close_colors(vboxes: Vec<VBox>, min_distance: u8) -> Vec<VBox> {
+    let min_distance_sq = min_distance as u32 * min_distance as u32;
+    let mut merged: Vec<VBox> = Vec::with_capacity(vboxes.len());
+    for vbox in vboxes {
+        match merged.iter_mut().find(|v| distance_sq(v.average, vbox.average) < min_distance_sq) {
+            Some(v) => {
+                let total = v.count + vbox.count;
+                if total != 0 {
+                    let count = v.count;
+                    let mix = |a: u8, b: u8| {
+                        ((a as u64 * count + b as u64 * vbox.count + total / 2) / total) as u8
+                    };
+                    v.average = Color::new(mix(v.average.r, vbox.average.r),
+                                           mix(v.average.g, vbox.average.g),
+                                           mix(v.average.b, vbox.average.b));
+                    v.alpha = mix(v.alpha, vbox.alpha);
+                }
+                v.count = total;
+            }
+            None => merged.push(vbox),
+        }
+    }
+
+    merged
+}
+
+/// Runs Lloyd's algorithm over histogram cells using box colors as initial centroids.
+fn refine(vboxes: &mut [VBox], histogram: &Histogram) {
+    let multiplier = histogram.multiplier() as u32;
+    let center = |i: u8| cmp::min(i as u32 * multiplier + multiplier / 2, 255) as u8;
+
+    for _ in 0..histogram.options.refine_iterations {
+        let mut sums = vec![[0u64; 5]; vboxes.len()];
+        for i in histogram.r_min..=histogram.r_max {
+            for j in histogram.g_min..=histogram.g_max {
+                for k in histogram.b_min..=histogram.b_max {
+                    let hval = histogram.get(i, j, k);
+                    if hval == 0 {
+                        continue;
+                    }
+
+                    let c = Color::new(center(i), center(j), center(k));
+                    let n = vboxes.iter()
+                        .map(|v| v.average)
+                        .enumerate()
+                        .min_by_key(|&(_, average)| distance_sq(average, c))
+                        .map(|(n, _)| n)
+                        .unwrap();
+
+                    let sum = &mut sums[n];
+                    sum[0] += hval * c.r as u64;
+                    sum[1] += hval * c.g as u64;
+                    sum[2] += hval * c.b as u64;
+                    sum[3] += hval;
+                    sum[4] += histogram.get_alpha(i, j, k).unwrap_or(255 * hval);
+                }
             }
-            queue.pop();
-
-            // Do the cut.
-            let vboxes = apply_median_cut(histogram, &mut vbox)?;
-            queue.push(vboxes.0.clone());
-            if let Some(ref vb) = vboxes.1 {
-                queue.push(vb.clone());
-                color += 1;
+        }
+
+        let mut changed = false;
+        for (vbox, sum) in vboxes.iter_mut().zip(sums.iter()) {
+            let total = sum[3];
+            if total == 0 {
+                // Keep colors without pixels as is.
+                continue;
             }
 
-            queue.sort_by(comparator);
+            let average = Color::new(((sum[0] + total / 2) / total) as u8,
+                                     ((sum[1] + total / 2) / total) as u8,
+                                     ((sum[2] + total / 2) / total) as u8);
+            changed |= average != vbox.average || total != vbox.count;
+            vbox.average = average;
+            vbox.alpha = ((sum[4] + total / 2) / total) as u8;
+            vbox.count = total;
+        }
+
+        if !changed {
+            break;
+        }
+    }
+}
 
-            if color >= target {
-               break;
+fn sort_vboxes(vboxes: &mut [VBox], order: SortOrder, mode: CompatMode) {
+    match order {
+        // The reference implementations return boxes by volume-weighted population.
+        SortOrder::Population if mode == CompatMode::ColorThiefPy => {}
+        SortOrder::Population => {
+            // Move the most populated box to the front, keeping the order of the rest.
+            // The first box wins on ties.
+            let most_populated = vboxes.iter().enumerate().max_by_key(|&(i, v)| (v.count, cmp::Reverse(i)));
+            if let Some((i, _)) = most_populated {
+                vboxes[..=i].rotate_right(1);
             }
         }
+        SortOrder::LuminanceAsc => {
+            vboxes.sort_by(|a, b| cmp_f32(color::relative_luminance(a.average), color::relative_luminance(b.average)));
+        }
+        SortOrder::LuminanceDesc => {
+            vboxes.sort_by(|a, b| cmp_f32(color::relative_luminance(b.average), color::relative_luminance(a.average)));
+        }
+        SortOrder::Hue => {
+            vboxes.sort_by(|a, b| cmp_f32(color::hue(a.average), color::hue(b.average)));
+        }
+    }
+}
+
+fn cmp_f32(a: f32, b: f32) -> cmp::Ordering {
+    a.partial_cmp(&b).unwrap_or(cmp::Ordering::Equal)
+}
+
+// Inner function to do the iteration.
+fn iterate(
+    queue: &mut PriorityQueue,
+    target: u8,
+    histogram: &Histogram,
+) -> Result<(), Error> {
+    let mut color = 1;
+
+    // Allocated once, since cuts happen many times.
+    let mut partial_sum = vec![0; histogram.vbox_length()];
+    let mut look_ahead_sum = vec![0; histogram.vbox_length()];
+
+    for _ in 0..histogram.options.max_iterations {
+        let vbox = match queue.pop() {
+            Some(vbox) => vbox,
+            None => break,
+        };
+
+        if vbox.count == 0 {
+            // Nothing left to cut, so the queue will not change anymore.
+            queue.push(vbox);
+            break;
+        }
+
+        // Do the cut.
+        let (vbox1, vbox2) = apply_median_cut(histogram, vbox, &mut partial_sum, &mut look_ahead_sum)?;
+        queue.push(vbox1);
+        if let Some(vbox2) = vbox2 {
+            queue.push(vbox2);
+            color += 1;
+        }
+
+        if color >= target {
+           break;
+        }
     }
 
     Ok(())
 }
 
+type Comparator = fn(&VBox, &VBox) -> cmp::Ordering;
+
+/// A max-heap of VBoxes ordered by a comparator.
+///
+/// Equal VBoxes are ordered by insertion, the last one is the greatest.
+/// This way it behaves exactly like a vector sorted with a stable sort after each push.
+struct PriorityQueue {
+    heap: BinaryHeap<QueueItem>,
+    comparator: Comparator,
+    seq: usize,
+}
+
+impl PriorityQueue {
+    fn new(comparator: Comparator) -> Self {
+        PriorityQueue {
+            heap: BinaryHeap::new(),
+            comparator,
+            seq: 0,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.heap.len()
+    }
+
+    fn push(&mut self, vbox: VBox) {
+        self.heap.push(QueueItem { vbox, seq: self.seq, comparator: self.comparator });
+        self.seq += 1;
+    }
+
+    fn pop(&mut self) -> Option<VBox> {
+        self.heap.pop().map(|item| item.vbox)
+    }
+
+    /// Returns VBoxes in ascending order.
+    fn into_sorted_vec(self) -> Vec<VBox> {
+        self.heap.into_sorted_vec().into_iter().map(|item| item.vbox).collect()
+    }
+
+    /// Returns copies of VBoxes in ascending order.
+    fn to_sorted_vec(&self) -> Vec<VBox> {
+        self.heap.clone().into_sorted_vec().into_iter().map(|item| item.vbox).collect()
+    }
+}
+
+#[derive(Clone)]
+struct QueueItem {
+    vbox: VBox,
+    seq: usize,
+    comparator: Comparator,
+}
+
+impl Ord for QueueItem {
+    fn cmp(&self, other: &Self) -> cmp::Ordering {
+        (self.comparator)(&self.vbox, &other.vbox).then(self.seq.cmp(&other.seq))
+    }
+}
+
+impl PartialOrd for QueueItem {
+    fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for QueueItem {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == cmp::Ordering::Equal
+    }
+}
+
+impl Eq for QueueItem {}
+
 fn compare_by_count(a: &VBox, b: &VBox) -> cmp::Ordering {
-    a.count.cmp(&b.count)
+    a.count.cmp(&b.count).then_with(|| compare_by_bounds(a, b))
 }
 
 fn compare_by_product(a: &VBox, b: &VBox) -> cmp::Ordering {
-    if a.count == b.count {
+    let ord = if a.count == b.count {
         // If count is 0 for both (or the same), sort by volume.
         a.volume.cmp(&b.volume)
     } else {
         // Otherwise sort by products.
-        let a_product = a.count as i64 * a.volume as i64;
-        let b_product = b.count as i64 * b.volume as i64;
+        let a_product = a.count as u128 * a.volume as u128;
+        let b_product = b.count as u128 * b.volume as u128;
         a_product.cmp(&b_product)
-    }
+    };
+
+    ord.then_with(|| compare_by_bounds(a, b))
+}
+
+// colorthief-py relies on a stable sort to resolve ties.
+fn compare_by_count_py(a: &VBox, b: &VBox) -> cmp::Ordering {
+    a.count.cmp(&b.count)
+}
+
+fn compare_by_product_py(a: &VBox, b: &VBox) -> cmp::Ordering {
+    let a_product = a.count as u128 * a.volume as u128;
+    let b_product = b.count as u128 * b.volume as u128;
+    a_product.cmp(&b_product)
+}
+
+/// Makes comparators total, so the order doesn't depend on the sorting algorithm.
+fn compare_by_bounds(a: &VBox, b: &VBox) -> cmp::Ordering {
+    (a.r_min, a.g_min, a.b_min, a.r_max, a.g_max, a.b_max)
+        .cmp(&(b.r_min, b.g_min, b.b_min, b.r_max, b.g_max, b.b_max))
+}
+
+/// `f64::ceil` is not available in `core`.
+fn ceil(n: f64) -> f64 {
+    let t = n as i64 as f64;
+    if t < n { t + 1.0 } else { t }
+}
+
+/// Returns a histogram cell index of a reduced color.
+///
+/// Uses the default 5 signal bits, so channels must be in a 0..32 range,
+/// which is an 8-bit value shifted right by 3.
+/// The index is `(r << 10) | (g << 5) | b`, like in `Histogram::counts`.
+///
+/// Panics in debug builds on larger channels,
+/// which would overlap other channels otherwise.
+pub fn color_index(r: u8, g: u8, b: u8) -> usize {
+    debug_assert!(r < 32 && g < 32 && b < 32, "channels must be in a 0..32 range");
+    make_color_index_of(r, g, b, SIGNAL_BITS)
+}
+
+/// Returns a reduced color of a histogram cell index.
+///
+/// The inverse of `color_index`.
+///
+/// Panics in debug builds on indices outside of a `0..32768` range.
+/// Upper bits are ignored otherwise.
+pub fn index_to_color(index: usize) -> (u8, u8, u8) {
+    debug_assert!(index < 1 << 15, "an index must be in a 0..32768 range");
+    color_of_index(index, SIGNAL_BITS)
+}
+
+/// Get reduced-space color for a color index.
+fn color_of_index(index: usize, signal_bits: u8) -> (u8, u8, u8) {
+    let mask = (1 << signal_bits) - 1;
+    (((index >> (2 * signal_bits)) & mask) as u8,
+     ((index >> signal_bits) & mask) as u8,
+     (index & mask) as u8)
 }
 
 /// Get reduced-space color index for a pixel.
-fn make_color_index_of(red: u8, green: u8, blue: u8) -> usize {
-    (   ((red as i32) << (2 * SIGNAL_BITS))
-      + ((green as i32) << SIGNAL_BITS)
+fn make_color_index_of(red: u8, green: u8, blue: u8, signal_bits: u8) -> usize {
+    (   ((red as i32) << (2 * signal_bits))
+      + ((green as i32) << signal_bits)
       +   blue as i32
     ) as usize
 }
@@ -0,0 +1,127 @@
+// Copyright 2017, Reizner Evgeniy <razrfalcon@gmail.com>.
+// See the COPYRIGHT file at the top-level directory of this distribution.
+// Licensed under the MIT license, see the LICENSE file or <http://opensource.org/licenses/MIT>
+
+//! Octree color quantization.
+//!
+//! Works on histogram cells, so it shares sampling and filtering with the median cut.
+
+use core::cmp;
+
+use alloc::vec::Vec;
+
+use {Histogram, VBox};
+
+struct Node {
+    /// Indexes of child nodes. 0 means no child, since it's the root index.
+    children: [usize; 8],
+    /// A number of samples in the subtree.
+    count: u64,
+    level: u8,
+    r: u8,
+    g: u8,
+    b: u8,
+    leaf: bool,
+}
+
+impl Node {
+    fn new(level: u8, r: u8, g: u8, b: u8) -> Self {
+        Node {
+            children: [0; 8],
+            count: 0,
+            level,
+            r,
+            g,
+            b,
+            leaf: false,
+        }
+    }
+}
+
+/// Returns at most `max_colors` boxes, one per octree leaf.
+///
+/// Boxes are sorted by population, the most populated one first.
+pub(crate) fn quantize(histogram: &Histogram, max_colors: u8) -> Vec<VBox> {
+    let depth = histogram.options.signal_bits;
+
+    let mut nodes = vec![Node::new(0, 0, 0, 0)];
+    let mut leaves = 0;
+    for r in histogram.r_min..=histogram.r_max {
+        for g in histogram.g_min..=histogram.g_max {
+            for b in histogram.b_min..=histogram.b_max {
+                let hval = histogram.get(r, g, b);
+                if hval == 0 {
+                    continue;
+                }
+
+                let mut index = 0;
+                nodes[index].count += hval;
+                for level in 1..=depth {
+                    let shift = depth - level;
+                    let (cr, cg, cb) = (r >> shift, g >> shift, b >> shift);
+                    let child = (((cr & 1) << 2) | ((cg & 1) << 1) | (cb & 1)) as usize;
+                    if nodes[index].children[child] == 0 {
+                        nodes[index].children[child] = nodes.len();
+                        nodes.push(Node::new(level, cr, cg, cb));
+                        if level == depth {
+                            leaves += 1;
+                        }
+                    }
+
+                    index = nodes[index].children[child];
+                    nodes[index].count += hval;
+                }
+
+                nodes[index].leaf = true;
+            }
+        }
+    }
+
+    // Reduce the least populated nodes first, starting from the deepest level.
+    // Children of a node are already leaves when it's reduced.
+    // The root is never reduced, extra leaves are truncated instead.
+    let mut reducible: Vec<usize> = (1..nodes.len()).filter(|&i| !nodes[i].leaf).collect();
+    reducible.sort_by(|&a, &b| {
+        nodes[b].level.cmp(&nodes[a].level)
+            .then(nodes[a].count.cmp(&nodes[b].count))
+            .then(a.cmp(&b))
+    });
+
+    for index in reducible {
+        if leaves <= max_colors as usize {
+            break;
+        }
+
+        let children = nodes[index].children.iter().filter(|&&c| c != 0).count();
+        nodes[index].children = [0; 8];
+        nodes[index].leaf = true;
+        leaves = leaves + 1 - children;
+    }
+
+    let mut vboxes = Vec::with_capacity(leaves);
+    let mut stack = vec![0];
+    while let Some(index) = stack.pop() {
+        let node = &nodes[index];
+        if node.leaf {
+            // A node is a cube of histogram cells.
+            let shift = depth - node.level;
+            let bounds = |c: u8| {
+                let min = (c as u32) << shift;
+                (min as u8, cmp::min(min + (1 << shift) - 1, 255) as u8)
+            };
+
+            let (r_min, r_max) = bounds(node.r);
+            let (g_min, g_max) = bounds(node.g);
+            let (b_min, b_max) = bounds(node.b);
+            let mut vbox = VBox::new(r_min, r_max, g_min, g_max, b_min, b_max);
+            vbox.recalc(histogram);
+            vboxes.push(vbox);
+        } else {
+            stack.extend(node.children.iter().rev().filter(|&&c| c != 0));
+        }
+    }
+
+    vboxes.sort_by_key(|v| cmp::Reverse(v.count));
+    vboxes.truncate(max_colors as usize);
+    vboxes
+}
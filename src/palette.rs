@@ -0,0 +1,165 @@
+// Copyright 2017, Reizner Evgeniy <razrfalcon@gmail.com>.
+// See the COPYRIGHT file at the top-level directory of this distribution.
+// Licensed under the MIT license, see the LICENSE file or <http://opensource.org/licenses/MIT>
+
+//! A palette result type.
+
+use core::cmp;
+use core::slice;
+
+use alloc::vec::Vec;
+
+use color::relative_luminance;
+use {get_vboxes, Color, ColorFormat, Error, Image, Options, VBox, RGBA8};
+
+/// A representative color palette of an image.
+///
+/// Always contains at least one color.
+///
+/// Deserialized palettes are checked the same way. `Error::InvalidPalette` otherwise.
+#[derive(Clone,PartialEq,Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "PaletteData"))]
+pub struct Palette {
+    colors: Vec<Color>,
+    counts: Vec<u64>,
+    pub(crate) transparent_average: Option<RGBA8>,
+}
+
+/// An unchecked `Palette`.
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+struct PaletteData {
+    colors: Vec<Color>,
+    counts: Vec<u64>,
+    transparent_average: Option<RGBA8>,
+}
+
+#[cfg(feature = "serde")]
+impl core::convert::TryFrom<PaletteData> for Palette {
+    type Error = Error;
+
+    fn try_from(data: PaletteData) -> Result<Self, Self::Error> {
+        if data.colors.is_empty() || data.colors.len() != data.counts.len() {
+            return Err(Error::InvalidPalette);
+        }
+
+        Ok(Palette {
+            colors: data.colors,
+            counts: data.counts,
+            transparent_average: data.transparent_average,
+        })
+    }
+}
+
+impl Palette {
+    pub(crate) fn from_vboxes(vboxes: &[VBox]) -> Self {
+        Palette {
+            colors: vboxes.iter().map(|v| v.average).collect(),
+            counts: vboxes.iter().map(|v| v.count).collect(),
+            transparent_average: None,
+        }
+    }
+
+    /// Returns the dominant color.
+    ///
+    /// The same as `get_dominant_color`, it's the most populated color of the palette.
+    pub fn dominant(&self) -> Color {
+        let mut index = 0;
+        for (i, &count) in self.counts.iter().enumerate() {
+            if count > self.counts[index] {
+                index = i;
+            }
+        }
+
+        self.colors[index]
+    }
+
+    /// Returns a copy of the palette sorted by relative luminance, from dark to light.
+    pub fn sorted_by_luminance(&self) -> Palette {
+        let mut indices: Vec<usize> = (0..self.colors.len()).collect();
+        indices.sort_by(|&a, &b| {
+            relative_luminance(self.colors[a])
+                .partial_cmp(&relative_luminance(self.colors[b]))
+                .unwrap_or(cmp::Ordering::Equal)
+        });
+
+        Palette {
+            colors: indices.iter().map(|&i| self.colors[i]).collect(),
+            counts: indices.iter().map(|&i| self.counts[i]).collect(),
+            transparent_average: self.transparent_average,
+        }
+    }
+
+    /// Returns a number of colors.
+    pub fn len(&self) -> usize {
+        self.colors.len()
+    }
+
+    /// Checks that the palette has no colors.
+    ///
+    /// Always `false`, since a palette has at least one color.
+    pub fn is_empty(&self) -> bool {
+        self.colors.is_empty()
+    }
+
+    /// Returns an iterator over colors.
+    pub fn iter(&self) -> slice::Iter<'_, Color> {
+        self.colors.iter()
+    }
+
+    /// Returns colors.
+    pub fn colors(&self) -> &[Color] {
+        &self.colors
+    }
+
+    /// Returns a number of pixels represented by each color.
+    ///
+    /// When pixels are weighted, like with `Options::saturation_weight`,
+    /// it's a sum of pixel weights, rounded.
+    ///
+    /// In the same order as `colors`.
+    pub fn counts(&self) -> &[u64] {
+        &self.counts
+    }
+
+    /// Returns an average color of skipped transparent pixels.
+    ///
+    /// Set only by `Quantizer::build` with `Options::transparent_average` enabled.
+    /// See `Histogram::transparent_average`.
+    pub fn transparent_average(&self) -> Option<RGBA8> {
+        self.transparent_average
+    }
+}
+
+impl<'a> IntoIterator for &'a Palette {
+    type Item = &'a Color;
+    type IntoIter = slice::Iter<'a, Color>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.colors.iter()
+    }
+}
+
+impl From<Palette> for Vec<Color> {
+    fn from(palette: Palette) -> Self {
+        palette.colors
+    }
+}
+
+/// Returns a representative color palette of an image.
+///
+/// Unlike `get_palette`, which is a shorthand returning only colors,
+/// also keeps a number of pixels represented by each color.
+///
+/// See `get_palette` for arguments description.
+pub fn palette(
+    pixels: &[u8],
+    color_format: ColorFormat,
+    quality: u8,
+    max_colors: u8,
+) -> Result<Palette, Error> {
+    let image = Image::checked(pixels, color_format)?;
+    let vboxes = get_vboxes(&image, quality, max_colors, &Options::default())?;
+    Ok(Palette::from_vboxes(&vboxes))
+}
@@ -0,0 +1,165 @@
+// Copyright 2017, Reizner Evgeniy <razrfalcon@gmail.com>.
+// See the COPYRIGHT file at the top-level directory of this distribution.
+// Licensed under the MIT license, see the LICENSE file or <http://opensource.org/licenses/MIT>
+
+//! SIMD histogram building.
+//!
+//! `std::simd` is not stable yet and the crate forbids `unsafe`,
+//! so pixels are filtered and bounded using the safe vectors of the `wide` crate.
+//! Produces exactly the same histogram as the scalar path.
+
+use core::ops;
+
+use wide::u8x16;
+
+use {color_parts, make_color_index_of, AlphaMode, Channel, ColorSpace, Histogram, Image, Sampling, SpatialWeight};
+
+/// A number of pixels processed per iteration.
+const LANES: usize = 16;
+
+/// Checks that pixels can be added without per-pixel weighting or conversion.
+pub(crate) fn is_supported(histogram: &Histogram) -> bool {
+    let options = &histogram.options;
+    options.spatial_weight == SpatialWeight::Uniform
+        && options.saturation_weight == 0.0
+        && options.alpha_mode == AlphaMode::Threshold
+        && options.color_space == ColorSpace::Rgb
+        && !options.transparent_average
+        && !options.snap_exact
+        && options.composite_over.is_none()
+}
+
+/// The same as `Histogram::add_samples`, but processes `LANES` samples at once.
+///
+/// Must be called only when `is_supported` returns `true`.
+pub(crate) fn add_samples<T: Channel>(
+    histogram: &mut Histogram,
+    image: &Image<T>,
+    step: usize,
+    samples: ops::Range<usize>,
+) {
+    let options = histogram.options;
+    let shift = 8 - options.signal_bits;
+    let zero = u8x16::splat(0);
+    let ones = u8x16::splat(u8::MAX);
+    let alpha_threshold = u8x16::splat(options.alpha_threshold);
+    let (check_white, white) = match options.ignore_white { Some(t) => (ones, t), None => (zero, 0) };
+    let (check_black, black) = match options.ignore_black { Some(t) => (ones, t), None => (zero, 0) };
+    let white = u8x16::splat(white);
+    let black = u8x16::splat(black);
+
+    let channels = image.color_format.channels();
+    let packed = !matches!(options.sampling, Sampling::Random { .. })
+        && image.mask.is_none()
+        && image.width.checked_mul(channels) == Some(image.stride);
+
+    // Unshifted bounds of all lanes. Shifting is monotonic, so it can be done once at the end.
+    let mut r_min = ones;
+    let mut r_max = zero;
+    let mut g_min = ones;
+    let mut g_max = zero;
+    let mut b_min = ones;
+    let mut b_max = zero;
+    let mut any_kept = false;
+
+    let mut start = samples.start;
+    while start < samples.end {
+        let len = core::cmp::min(LANES, samples.end - start);
+
+        let mut valid = [0u8; LANES];
+        let mut r = [0u8; LANES];
+        let mut g = [0u8; LANES];
+        let mut b = [0u8; LANES];
+        let mut a = [0u8; LANES];
+        if packed {
+            // The same as `Image::pixel`, without per-pixel layout checks.
+            for i in 0..len {
+                let pos = image.offset + (start + i) * step * channels;
+                let p = color_parts(image.pixels, image.color_format, pos);
+                valid[i] = u8::MAX;
+                r[i] = p.0;
+                g[i] = p.1;
+                b[i] = p.2;
+                a[i] = p.3;
+            }
+        } else {
+            for i in 0..len {
+                if let Some(index) = options.sample_index(image, step, start + i) {
+                    let p = image.pixel(index);
+                    valid[i] = u8::MAX;
+                    r[i] = p.0;
+                    g[i] = p.1;
+                    b[i] = p.2;
+                    a[i] = p.3;
+                }
+            }
+        }
+
+        let valid = u8x16::new(valid);
+        let vr = u8x16::new(r);
+        let vg = u8x16::new(g);
+        let vb = u8x16::new(b);
+        let va = u8x16::new(a);
+
+        let transparent = valid & less(va, alpha_threshold);
+        let visible = valid & !transparent;
+        let is_white = visible & check_white
+            & greater(vr, white) & greater(vg, white) & greater(vb, white);
+        let is_black = check_black & less(vr, black) & less(vg, black) & less(vb, black);
+        let keep = visible & !is_white & !is_black;
+
+        // Skipped lanes must not affect the bounds.
+        r_min = r_min.min(vr | !keep);
+        r_max = r_max.max(vr & keep);
+        g_min = g_min.min(vg | !keep);
+        g_max = g_max.max(vg & keep);
+        b_min = b_min.min(vb | !keep);
+        b_max = b_max.max(vb & keep);
+
+        histogram.transparent += transparent.move_mask().count_ones() as u64;
+        histogram.white += is_white.move_mask().count_ones() as u64;
+
+        let mut mask = keep.move_mask() as u32;
+        any_kept |= mask != 0;
+        while mask != 0 {
+            let i = mask.trailing_zeros() as usize;
+            mask &= mask - 1;
+
+            let index = make_color_index_of(r[i] >> shift, g[i] >> shift, b[i] >> shift, options.signal_bits);
+            histogram.data[index] += 1;
+            if !histogram.alpha.is_empty() {
+                histogram.alpha[index] += a[i] as u64;
+            }
+        }
+
+        start += len;
+    }
+
+    // Bounds stay unchanged when all pixels were skipped.
+    if !any_kept {
+        return;
+    }
+
+    let reduce_min = |v: u8x16| v.to_array().iter().cloned().min().unwrap_or(u8::MAX) >> shift;
+    let reduce_max = |v: u8x16| v.to_array().iter().cloned().max().unwrap_or(0) >> shift;
+    histogram.r_min = histogram.r_min.min(reduce_min(r_min));
+    histogram.r_max = histogram.r_max.max(reduce_max(r_max));
+    histogram.g_min = histogram.g_min.min(reduce_min(g_min));
+    histogram.g_max = histogram.g_max.max(reduce_max(g_max));
+    histogram.b_min = histogram.b_min.min(reduce_min(b_min));
+    histogram.b_max = histogram.b_max.max(reduce_max(b_max));
+}
+
+/// Returns `u8::MAX` in lanes where `a > b` and zeros otherwise.
+///
+/// `wide` has no unsigned comparisons, but `a - b` saturates to zero when `a <= b`.
+#[inline]
+fn greater(a: u8x16, b: u8x16) -> u8x16 {
+    !a.saturating_sub(b).cmp_eq(u8x16::splat(0))
+}
+
+/// Returns `u8::MAX` in lanes where `a < b` and zeros otherwise.
+#[inline]
+fn less(a: u8x16, b: u8x16) -> u8x16 {
+    greater(b, a)
+}
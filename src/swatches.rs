@@ -0,0 +1,105 @@
+// Copyright 2017, Reizner Evgeniy <razrfalcon@gmail.com>.
+// See the COPYRIGHT file at the top-level directory of this distribution.
+// Licensed under the MIT license, see the LICENSE file or <http://opensource.org/licenses/MIT>
+
+//! Android Palette-style named swatches.
+
+use alloc::vec::Vec;
+
+use color::to_hsl;
+use {get_vboxes, Color, ColorFormat, Error, Image, Options};
+
+/// A number of palette colors to choose swatches from.
+const MAX_COLORS: u8 = 16;
+
+const WEIGHT_SATURATION: f32 = 0.24;
+const WEIGHT_LIGHTNESS: f32 = 0.52;
+const WEIGHT_POPULATION: f32 = 0.24;
+
+/// Named colors of an image.
+///
+/// A slot is `None` when the palette has no suitable color.
+#[allow(missing_docs)]
+#[derive(Clone,Copy,PartialEq,Debug,Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Swatches {
+    pub vibrant: Option<Color>,
+    pub light_vibrant: Option<Color>,
+    pub dark_vibrant: Option<Color>,
+    pub muted: Option<Color>,
+    pub light_muted: Option<Color>,
+    pub dark_muted: Option<Color>,
+}
+
+/// Min, target and max values.
+type Range = (f32, f32, f32);
+
+struct Target {
+    saturation: Range,
+    lightness: Range,
+}
+
+// The same values as in Android's Palette, in the same order.
+const LIGHT_VIBRANT: Target = Target { saturation: (0.35, 1.0, 1.0), lightness: (0.55, 0.74, 1.0) };
+const VIBRANT: Target       = Target { saturation: (0.35, 1.0, 1.0), lightness: (0.3, 0.5, 0.7) };
+const DARK_VIBRANT: Target  = Target { saturation: (0.35, 1.0, 1.0), lightness: (0.0, 0.26, 0.45) };
+const LIGHT_MUTED: Target   = Target { saturation: (0.0, 0.3, 0.4), lightness: (0.55, 0.74, 1.0) };
+const MUTED: Target         = Target { saturation: (0.0, 0.3, 0.4), lightness: (0.3, 0.5, 0.7) };
+const DARK_MUTED: Target    = Target { saturation: (0.0, 0.3, 0.4), lightness: (0.0, 0.26, 0.45) };
+
+/// Returns named swatches of an image.
+///
+/// Colors are chosen from the image palette by their saturation, lightness and population.
+/// Each palette color is used only once.
+///
+/// See `get_palette` for arguments description.
+pub fn swatches(
+    pixels: &[u8],
+    color_format: ColorFormat,
+    quality: u8,
+) -> Result<Swatches, Error> {
+    let image = Image::checked(pixels, color_format)?;
+    let vboxes = get_vboxes(&image, quality, MAX_COLORS, &Options::default())?;
+
+    let colors: Vec<_> = vboxes.iter()
+        .filter(|v| v.count != 0)
+        .map(|v| (v.average, to_hsl(&v.average), v.count))
+        .collect();
+    let max_population = colors.iter().map(|c| c.2).max().unwrap_or(0);
+
+    let mut used = vec![false; colors.len()];
+    let mut pick = |target: Target| {
+        let mut best: Option<(usize, f32)> = None;
+        for (i, &(_, (_, s, l), population)) in colors.iter().enumerate() {
+            if used[i] || !in_range(s, target.saturation) || !in_range(l, target.lightness) {
+                continue;
+            }
+
+            let score = WEIGHT_SATURATION * (1.0 - (s - target.saturation.1).abs())
+                      + WEIGHT_LIGHTNESS * (1.0 - (l - target.lightness.1).abs())
+                      + WEIGHT_POPULATION * (population as f32 / max_population as f32);
+
+            if best.is_none_or(|(_, best_score)| score > best_score) {
+                best = Some((i, score));
+            }
+        }
+
+        best.map(|(i, _)| {
+            used[i] = true;
+            colors[i].0
+        })
+    };
+
+    Ok(Swatches {
+        light_vibrant: pick(LIGHT_VIBRANT),
+        vibrant: pick(VIBRANT),
+        dark_vibrant: pick(DARK_VIBRANT),
+        light_muted: pick(LIGHT_MUTED),
+        muted: pick(MUTED),
+        dark_muted: pick(DARK_MUTED),
+    })
+}
+
+fn in_range(v: f32, range: Range) -> bool {
+    v >= range.0 && v <= range.2
+}
@@ -0,0 +1,88 @@
+// Copyright 2017, Reizner Evgeniy <razrfalcon@gmail.com>.
+// See the COPYRIGHT file at the top-level directory of this distribution.
+// Licensed under the MIT license, see the LICENSE file or <http://opensource.org/licenses/MIT>
+
+//! Area-average downscaling for `Sampling::Downscale`.
+
+use core::cmp;
+
+use alloc::vec::Vec;
+
+use {Channel, ColorFormat, Image};
+
+/// A downscaled RGBA copy of an image.
+pub(crate) struct Thumbnail {
+    pixels: Vec<u8>,
+    /// Cells without any unmasked pixel have a zero mask value.
+    mask: Option<Vec<u8>>,
+    width: usize,
+}
+
+impl Thumbnail {
+    /// Downscales an image by an integer factor, so both sides are at most `max_dim` pixels.
+    ///
+    /// Each thumbnail pixel is an average of a block of image pixels.
+    /// Colors are weighted by alpha, so transparent pixels do not tint their neighbors.
+    pub(crate) fn new<T: Channel>(image: &Image<T>, max_dim: u32) -> Self {
+        let width = image.width;
+        if width == 0 || image.pixel_count == 0 {
+            return Thumbnail { pixels: Vec::new(), mask: None, width: 0 };
+        }
+
+        let height = image.pixel_count.div_ceil(width);
+        let factor = cmp::max(cmp::max(width, height).div_ceil(cmp::max(max_dim, 1) as usize), 1);
+        let thumb_width = width.div_ceil(factor);
+        let thumb_height = height.div_ceil(factor);
+
+        // Premultiplied color sums, an alpha sum and a number of pixels.
+        let mut sums = vec![[0u64; 5]; thumb_width * thumb_height];
+        for index in 0..image.pixel_count {
+            if let Some(mask) = image.mask {
+                if mask[index] == 0 {
+                    continue;
+                }
+            }
+
+            let (r, g, b, a) = image.pixel(index);
+            let cell = (index / width / factor) * thumb_width + (index % width) / factor;
+            let sum = &mut sums[cell];
+            sum[0] += r as u64 * a as u64;
+            sum[1] += g as u64 * a as u64;
+            sum[2] += b as u64 * a as u64;
+            sum[3] += a as u64;
+            sum[4] += 1;
+        }
+
+        let mut pixels = Vec::with_capacity(sums.len() * 4);
+        for sum in &sums {
+            pixels.extend_from_slice(&[
+                div_round(sum[0], sum[3]),
+                div_round(sum[1], sum[3]),
+                div_round(sum[2], sum[3]),
+                div_round(sum[3], sum[4]),
+            ]);
+        }
+
+        let mask = image.mask.map(|_| sums.iter().map(|sum| (sum[4] != 0) as u8).collect());
+
+        Thumbnail { pixels, mask, width: thumb_width }
+    }
+
+    pub(crate) fn image(&self) -> Image<'_, u8> {
+        Image {
+            pixels: &self.pixels,
+            color_format: ColorFormat::Rgba,
+            width: self.width,
+            stride: self.width * 4,
+            offset: 0,
+            pixel_count: self.pixels.len() / 4,
+            mask: self.mask.as_deref(),
+            sized: true,
+        }
+    }
+}
+
+/// Returns a rounded `n / d` or 0 when `d` is 0.
+fn div_round(n: u64, d: u64) -> u8 {
+    (n + d / 2).checked_div(d).unwrap_or(0) as u8
+}
@@ -1,9 +1,11 @@
 extern crate image;
 extern crate color_thief;
+#[cfg(feature = "serde")]
+extern crate serde;
 
 use std::path;
 
-use color_thief::{Color, ColorFormat};
+use color_thief::{Algorithm, Color, ColorFormat, Rect, Sampling, SortOrder, SpatialWeight};
 
 fn get_image_buffer(img: image::DynamicImage) -> (Vec<u8>, ColorFormat) {
     match img {
@@ -17,15 +19,21 @@ fn get_image_buffer(img: image::DynamicImage) -> (Vec<u8>, ColorFormat) {
     }
 }
 
+fn sorted(mut colors: Vec<Color>) -> Vec<Color> {
+    colors.sort_by_key(|c| (c.r, c.g, c.b));
+    colors
+}
+
 #[test]
 fn image1() {
-    let img = image::open(&path::Path::new("images/photo1.jpg")).unwrap();
+    let img = image::open(path::Path::new("images/photo1.jpg")).unwrap();
     let (buffer, color_type) = get_image_buffer(img);
     let colors = color_thief::get_palette(&buffer, color_type, 10, 10).unwrap();
 
-    assert_eq!(colors[0], Color::new( 54,  37,  28)); //  55,  37,  29
-    assert_eq!(colors[1], Color::new(215, 195, 134)); // 213, 193, 136
-    assert_eq!(colors[2], Color::new(109, 204, 223)); // 110, 204, 223
+    // The most populated color goes first.
+    assert_eq!(colors[0], Color::new(109, 204, 223)); // 110, 204, 223
+    assert_eq!(colors[1], Color::new( 54,  37,  28)); //  55,  37,  29
+    assert_eq!(colors[2], Color::new(215, 195, 134)); // 213, 193, 136
     assert_eq!(colors[3], Color::new(127, 119,  58)); // 131, 122,  58
     assert_eq!(colors[4], Color::new( 43, 125, 149)); //  43, 124, 148
     assert_eq!(colors[5], Color::new(134, 123, 107)); // 156, 175, 121
@@ -36,7 +44,7 @@ fn image1() {
 
 #[test]
 fn image2() {
-    let img = image::open(&path::Path::new("images/iguana.png")).unwrap();
+    let img = image::open(path::Path::new("images/iguana.png")).unwrap();
     let (buffer, color_type) = get_image_buffer(img);
     let colors = color_thief::get_palette(&buffer, color_type, 10, 10).unwrap();
 
@@ -50,3 +58,2055 @@ fn image2() {
     assert_eq!(colors[7], Color::new( 22,  20,  27));
     assert_eq!(colors[8], Color::new(180, 148, 116));
 }
+
+#[test]
+fn dominant_color() {
+    let img = image::open(path::Path::new("images/photo1.jpg")).unwrap();
+    let (buffer, color_type) = get_image_buffer(img);
+    let color = color_thief::get_dominant_color(&buffer, color_type, 10).unwrap();
+
+    assert_eq!(color, Color::new(124, 190, 192));
+}
+
+#[test]
+fn dominant_first() {
+    for path in &["images/photo1.jpg", "images/iguana.png"] {
+        let img = image::open(path::Path::new(path)).unwrap();
+        let (buffer, color_type) = get_image_buffer(img);
+        for &quality in &[1, 5, 10] {
+            let colors = color_thief::get_palette(&buffer, color_type, quality, 5).unwrap();
+            assert_eq!(colors[0], color_thief::get_dominant_color(&buffer, color_type, quality).unwrap());
+
+            let counts = color_thief::get_palette_with_counts(&buffer, color_type, quality, 10).unwrap();
+            assert!(counts.iter().all(|c| c.1 <= counts[0].1));
+        }
+    }
+}
+
+#[test]
+fn palette_with_counts() {
+    let img = image::open(path::Path::new("images/iguana.png")).unwrap();
+    let (buffer, color_type) = get_image_buffer(img);
+    let colors = color_thief::get_palette(&buffer, color_type, 10, 10).unwrap();
+    let counts = color_thief::get_palette_with_counts(&buffer, color_type, 10, 10).unwrap();
+
+    assert_eq!(colors.len(), counts.len());
+    for (color, &(color2, count)) in colors.iter().zip(counts.iter()) {
+        assert_eq!(*color, color2);
+        assert!(count > 0);
+    }
+}
+
+#[test]
+fn palette_with_weights() {
+    let mut buffer = Vec::new();
+    for i in 0..1200 {
+        let c = if i < 900 { [200, 20, 20] } else { [20, 20, 200] };
+        buffer.extend_from_slice(&c);
+    }
+
+    let weights = color_thief::get_palette_with_weights(&buffer, ColorFormat::Rgb, 1, 2).unwrap();
+    assert_eq!(weights.len(), 2);
+    assert!(weights.contains(&(Color::new(204, 20, 20), 0.75)));
+    assert!(weights.contains(&(Color::new(20, 20, 204), 0.25)));
+
+    let img = image::open(path::Path::new("images/iguana.png")).unwrap();
+    let (buffer, color_type) = get_image_buffer(img);
+    let weights = color_thief::get_palette_with_weights(&buffer, color_type, 10, 10).unwrap();
+    let total: f32 = weights.iter().map(|w| w.1).sum();
+    assert!((total - 1.0).abs() < 0.0001);
+}
+
+#[test]
+fn alpha_threshold() {
+    let mut buffer = Vec::new();
+    for i in 0..1000 {
+        let c = if i < 500 { [200, 20, 20, 100] } else { [20, 20, 200, 100] };
+        buffer.extend_from_slice(&c);
+    }
+
+    assert_eq!(color_thief::get_palette(&buffer, ColorFormat::Rgba, 1, 2),
+               Err(color_thief::Error::NoVisiblePixels));
+
+    let options = color_thief::Options { alpha_threshold: 0, ..Default::default() };
+    let colors = color_thief::get_palette_with_options(
+        &buffer, ColorFormat::Rgba, 1, 2, &options).unwrap();
+    assert_eq!(colors.len(), 2);
+    assert!(colors.contains(&Color::new(204, 20, 20)));
+    assert!(colors.contains(&Color::new(20, 20, 204)));
+}
+
+#[test]
+fn ignore_white() {
+    let mut buffer = vec![255; 3 * 900];
+    buffer.extend_from_slice(&[10; 3 * 100]);
+
+    let colors = color_thief::get_palette(&buffer, ColorFormat::Rgb, 1, 2).unwrap();
+    assert_eq!(colors[0], Color::new(12, 12, 12));
+
+    let options = color_thief::Options { ignore_white: None, ..Default::default() };
+    let colors = color_thief::get_palette_with_options(
+        &buffer, ColorFormat::Rgb, 1, 2, &options).unwrap();
+    assert_eq!(colors[0], Color::new(252, 252, 252));
+}
+
+#[test]
+fn abgr() {
+    let mut buffer = Vec::new();
+    for i in 0..1000 {
+        let c = if i < 500 { [255, 20, 20, 200] } else { [100, 200, 20, 20] };
+        buffer.extend_from_slice(&c);
+    }
+
+    // Transparent blue pixels are skipped.
+    let colors = color_thief::get_palette(&buffer, ColorFormat::Abgr, 1, 2).unwrap();
+    assert_eq!(colors[0], Color::new(204, 20, 20));
+    assert!(!colors.contains(&Color::new(20, 20, 204)));
+}
+
+#[test]
+fn padded_formats() {
+    // The padding byte is not an alpha.
+    let mut buffer = Vec::new();
+    for i in 0..1000 {
+        let c = if i < 500 { [200, 20, 20, 0] } else { [20, 20, 200, 0] };
+        buffer.extend_from_slice(&c);
+    }
+
+    assert_eq!(color_thief::get_palette(&buffer, ColorFormat::Rgba, 1, 2),
+               Err(color_thief::Error::NoVisiblePixels));
+
+    let colors = color_thief::get_palette(&buffer, ColorFormat::Rgbx, 1, 2).unwrap();
+    assert_eq!(colors.len(), 2);
+    assert!(colors.contains(&Color::new(204, 20, 20)));
+    assert!(colors.contains(&Color::new(20, 20, 204)));
+
+    let colors = color_thief::get_palette(&buffer, ColorFormat::Bgrx, 1, 2).unwrap();
+    assert_eq!(colors.len(), 2);
+    assert!(colors.contains(&Color::new(204, 20, 20)));
+    assert!(colors.contains(&Color::new(20, 20, 204)));
+}
+
+#[test]
+fn cmyk() {
+    let mut buffer = Vec::new();
+    for i in 0..1000 {
+        // Red and a 50% gray.
+        let c = if i < 500 { [0, 255, 255, 50] } else { [0, 0, 0, 128] };
+        buffer.extend_from_slice(&c);
+    }
+
+    let colors = color_thief::get_palette(&buffer, ColorFormat::Cmyk, 1, 2).unwrap();
+    assert_eq!(colors.len(), 2);
+    assert!(colors.contains(&Color::new(204, 4, 4)));
+    assert!(colors.contains(&Color::new(124, 124, 124)));
+}
+
+#[test]
+fn ycbcr() {
+    let mut buffer = Vec::new();
+    for i in 0..1000 {
+        // Red and a 50% gray.
+        let c = if i < 500 { [76, 85, 255] } else { [128, 128, 128] };
+        buffer.extend_from_slice(&c);
+    }
+
+    let colors = color_thief::get_palette(&buffer, ColorFormat::YCbCr, 1, 2).unwrap();
+    assert_eq!(colors.len(), 2);
+    assert!(colors.contains(&Color::new(252, 4, 4)));
+    assert!(colors.contains(&Color::new(132, 132, 132)));
+}
+
+#[test]
+fn gray() {
+    let buffer: Vec<u8> = (0..1000).map(|i| (i / 4) as u8).collect();
+    let colors = color_thief::get_palette(&buffer, ColorFormat::Gray, 1, 5).unwrap();
+
+    assert_eq!(colors.len(), 5);
+    for c in colors {
+        assert!(c.r == c.g && c.g == c.b);
+    }
+}
+
+#[test]
+fn gray_alpha() {
+    let mut buffer = Vec::new();
+    for i in 0..1000 {
+        let c = if i < 500 { [40, 255] } else { [160, 0] };
+        buffer.extend_from_slice(&c);
+    }
+
+    let colors = color_thief::get_palette(&buffer, ColorFormat::GrayAlpha, 1, 2).unwrap();
+    assert_eq!(colors[0], Color::new(44, 44, 44));
+}
+
+#[test]
+fn rgb16() {
+    let img = image::open(path::Path::new("images/photo1.jpg")).unwrap();
+    let (buffer, color_type) = get_image_buffer(img);
+    let buffer16: Vec<u16> = buffer.iter().map(|&c| (c as u16) << 8 | c as u16).collect();
+
+    let colors = color_thief::get_palette(&buffer, color_type, 10, 10).unwrap();
+    let colors16 = color_thief::get_palette_u16(&buffer16, color_type, 10, 10).unwrap();
+    assert_eq!(colors, colors16);
+}
+
+#[test]
+fn rgba_premultiplied() {
+    let mut buffer = Vec::new();
+    for _ in 0..1000 {
+        buffer.extend_from_slice(&[100, 50, 25, 128]);
+    }
+
+    let colors = color_thief::get_palette(&buffer, ColorFormat::RgbaPremultiplied, 1, 2).unwrap();
+    assert_eq!(colors[0], Color::new(196, 100, 52));
+}
+
+#[test]
+fn quantizer() {
+    let img = image::open(path::Path::new("images/iguana.png")).unwrap();
+    let (buffer, color_type) = get_image_buffer(img);
+
+    let colors = color_thief::get_palette(&buffer, color_type, 10, 10).unwrap();
+    let colors2 = color_thief::Quantizer::new().build_palette(&buffer, color_type).unwrap();
+    assert_eq!(colors, colors2);
+
+    let q = color_thief::Quantizer::new();
+    assert_eq!(q.clone().quality(11).build_palette(&buffer, color_type),
+               Err(color_thief::Error::InvalidQuality));
+    assert_eq!(q.clone().max_colors(1).build_palette(&buffer, color_type),
+               Err(color_thief::Error::InvalidMaxColors));
+}
+
+#[test]
+fn empty_image() {
+    assert_eq!(color_thief::get_palette(&[], ColorFormat::Rgb, 10, 10),
+               Err(color_thief::Error::EmptyImage));
+}
+
+#[test]
+fn only_white() {
+    let white = Color::new(255, 255, 255);
+    assert_eq!(color_thief::get_palette(&[255; 30], ColorFormat::Rgb, 10, 10), Ok(vec![white]));
+    assert_eq!(color_thief::get_dominant_color(&[252; 30], ColorFormat::Rgb, 1), Ok(white));
+
+    let quantizer = color_thief::Quantizer::new().white_fallback(false);
+    assert_eq!(quantizer.build_palette(&[255; 30], ColorFormat::Rgb),
+               Err(color_thief::Error::OnlyWhite));
+
+    for bits in 1..9 {
+        let quantizer = color_thief::Quantizer::new().signal_bits(bits);
+        assert_eq!(quantizer.build_palette(&[255; 30], ColorFormat::Rgb), Ok(vec![white]));
+    }
+
+    // Transparent white pixels are still transparent.
+    assert_eq!(color_thief::get_palette(&[255, 255, 255, 0].repeat(10), ColorFormat::Rgba, 1, 10),
+               Err(color_thief::Error::NoVisiblePixels));
+}
+
+#[test]
+fn pure_red() {
+    let is_red = |c: Color| c.r > 240 && c.g < 16 && c.b < 16;
+
+    let buffer = [255, 0, 0].repeat(100);
+    let color = color_thief::get_dominant_color(&buffer, ColorFormat::Rgb, 1).unwrap();
+    assert!(is_red(color), "{:?}", color);
+    let palette = color_thief::get_palette(&buffer, ColorFormat::Rgb, 1, 10).unwrap();
+    assert!(is_red(palette[0]), "{:?}", palette);
+
+    // Pink pixels must not pull red towards them.
+    let mut buffer = [255, 0, 0].repeat(100);
+    buffer.extend([250, 200, 200].repeat(20));
+    let color = color_thief::get_dominant_color(&buffer, ColorFormat::Rgb, 1).unwrap();
+    assert!(is_red(color), "{:?}", color);
+    let palette = color_thief::palette(&buffer, ColorFormat::Rgb, 1, 2).unwrap();
+    assert!(is_red(palette.dominant()), "{:?}", palette);
+}
+
+#[test]
+fn average_color() {
+    use color_thief::color::{average_color, average_color_weighted};
+
+    assert_eq!(average_color(&[]), None);
+    assert_eq!(average_color_weighted(&[(Color::new(1, 2, 3), 0)]), None);
+
+    let red = Color::new(255, 0, 0);
+    let blue = Color::new(0, 0, 255);
+    assert_eq!(average_color(&[red]), Some(red));
+    // Not a muddy (128, 0, 128).
+    assert_eq!(average_color(&[red, blue]), Some(Color::new(188, 0, 188)));
+    assert_eq!(average_color_weighted(&[(red, 3), (blue, 0)]), Some(red));
+
+    let white = Color::new(255, 255, 255);
+    let black = Color::new(0, 0, 0);
+    let c = average_color_weighted(&[(white, 1), (black, 3)]).unwrap();
+    assert_eq!(c, Color::new(137, 137, 137));
+}
+
+#[test]
+fn rounding_mode() {
+    use color_thief::RoundingMode;
+
+    // The gray box average is 6.67.
+    let mut buffer = [0, 0, 0].repeat(30);
+    buffer.extend([8, 8, 8].repeat(15));
+    buffer.extend([200, 0, 0].repeat(90));
+
+    let palette = |rounding| {
+        color_thief::Quantizer::new().quality(1).max_colors(2).rounding(rounding)
+            .build_palette(&buffer, ColorFormat::Rgb).unwrap()
+    };
+
+    assert_eq!(palette(RoundingMode::Truncate), color_thief::get_palette(&buffer, ColorFormat::Rgb, 1, 2).unwrap());
+    assert!(palette(RoundingMode::Truncate).contains(&Color::new(6, 6, 6)));
+    assert!(palette(RoundingMode::Nearest).contains(&Color::new(7, 7, 7)));
+}
+
+#[test]
+fn color_index() {
+    assert_eq!(color_thief::color_index(0, 0, 0), 0);
+    assert_eq!(color_thief::color_index(1, 2, 3), (1 << 10) | (2 << 5) | 3);
+    assert_eq!(color_thief::index_to_color(color_thief::color_index(31, 0, 17)), (31, 0, 17));
+
+    // Matches the histogram layout.
+    let mut histogram = color_thief::Histogram::new();
+    histogram.add_frame(&[200, 100, 50].repeat(10), ColorFormat::Rgb, 1).unwrap();
+    let index = color_thief::color_index(200 >> 3, 100 >> 3, 50 >> 3);
+    assert!(histogram.counts()[index] > 0);
+    assert_eq!(histogram.counts().iter().filter(|&&n| n > 0).count(), 1);
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic]
+fn color_index_out_of_range() {
+    color_thief::color_index(200, 100, 50);
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic]
+fn index_to_color_out_of_range() {
+    color_thief::index_to_color(1 << 15);
+}
+
+#[test]
+fn transparent_average() {
+    let mut buffer = [200, 0, 0, 255].repeat(32);
+    buffer.extend([0, 0, 100, 0].repeat(16));
+    buffer.extend([0, 0, 200, 100].repeat(16));
+
+    let quantizer = color_thief::Quantizer::new().quality(1).max_colors(2);
+    let palette = quantizer.clone().build(&buffer, ColorFormat::Rgba).unwrap();
+    assert_eq!(palette.transparent_average(), None);
+
+    let palette = quantizer.transparent_average(true).build(&buffer, ColorFormat::Rgba).unwrap();
+    assert_eq!(palette.dominant(), Color::new(204, 4, 4));
+    assert_eq!(palette.transparent_average(), Some(color_thief::RGBA8::new(0, 0, 150, 50)));
+
+    let mut histogram = color_thief::Histogram::new();
+    histogram.add_frame(&buffer, ColorFormat::Rgba, 1).unwrap();
+    assert_eq!(histogram.transparent_average(), None);
+}
+
+#[test]
+fn max_pixels() {
+    use color_thief::{Error, HistogramBuilder, Options, Quantizer};
+
+    let buffer = [200, 20, 20].repeat(100);
+    let quantizer = Quantizer::new().quality(1).max_colors(2);
+    assert!(quantizer.clone().max_pixels(Some(100)).build_palette(&buffer, ColorFormat::Rgb).is_ok());
+    assert_eq!(quantizer.clone().max_pixels(Some(99)).build_palette(&buffer, ColorFormat::Rgb),
+               Err(Error::ImageTooLarge));
+    assert_eq!(quantizer.max_pixels(Some(99)).build_palette_with_progress(&buffer, ColorFormat::Rgb, &mut |_| true),
+               Err(Error::ImageTooLarge));
+
+    let options = Options { max_pixels: Some(150), ..Options::default() };
+    let mut builder = HistogramBuilder::with_options(options).unwrap();
+    assert!(builder.push_pixels(&buffer, ColorFormat::Rgb).is_ok());
+    assert_eq!(builder.push_pixels(&buffer, ColorFormat::Rgb), Err(Error::ImageTooLarge));
+    assert!(builder.push_pixels(&buffer[..150], ColorFormat::Rgb).is_ok());
+}
+
+#[test]
+fn palette_into() {
+    let img = image::open(path::Path::new("images/iguana.png")).unwrap();
+    let (buffer, color_type) = get_image_buffer(img);
+
+    let mut colors = vec![Color::new(1, 2, 3)];
+    color_thief::get_palette_into(&buffer, color_type, 10, 10, &mut colors).unwrap();
+    assert_eq!(colors, color_thief::get_palette(&buffer, color_type, 10, 10).unwrap());
+
+    let mut histogram = color_thief::Histogram::new();
+    histogram.add_frame(&buffer, color_type, 10).unwrap();
+    histogram.quantize_into(5, &mut colors).unwrap();
+    assert_eq!(colors, histogram.quantize(5).unwrap());
+
+    assert_eq!(color_thief::get_palette_into(&buffer, color_type, 11, 10, &mut colors),
+               Err(color_thief::Error::InvalidQuality));
+    assert!(colors.is_empty());
+}
+
+#[test]
+fn palette_indexed() {
+    let palette = [Color::new(200, 20, 20), Color::new(20, 20, 200), Color::new(20, 200, 20)];
+
+    // 1-bit indices, mostly red.
+    let indices = [0b0001_0000; 10];
+    let colors = color_thief::get_palette_indexed(&indices, 1, 8, &palette, 1, 2).unwrap();
+    assert_eq!(colors, vec![Color::new(204, 20, 20), Color::new(20, 20, 204)]);
+
+    // The same as expanded pixels.
+    let indices = [0b00_01_10_00, 0b10_10_00_00];
+    let pixels: Vec<_> = [0, 1, 2, 0, 2, 2, 0, 0].iter().map(|&i| palette[i]).collect();
+    assert_eq!(color_thief::get_palette_indexed(&indices, 2, 8, &palette, 1, 3),
+               color_thief::get_palette_from_iter(pixels, 1, 3));
+
+    // Rows of 3 pixels are padded to a byte, padding bits are not pixels.
+    let indices = [0b01_01_10_00, 0b01_10_10_00];
+    let pixels: Vec<_> = [1, 1, 2, 1, 2, 2].iter().map(|&i| palette[i]).collect();
+    assert_eq!(color_thief::get_palette_indexed(&indices, 2, 3, &palette, 1, 3),
+               color_thief::get_palette_from_iter(pixels, 1, 3));
+
+    // 4 bytes per row of 29 blue pixels, 3 padding bits with a red index each.
+    let indices = [0xFF, 0xFF, 0xFF, 0xF8].repeat(3);
+    assert_eq!(color_thief::get_palette_indexed(&indices, 1, 29, &palette, 1, 2),
+               color_thief::get_palette_from_iter(vec![palette[1]; 87], 1, 2));
+
+    assert_eq!(color_thief::get_palette_indexed(&[0x22], 4, 2, &palette, 1, 2).map(|c| c[0]),
+               Ok(Color::new(20, 204, 20)));
+    assert_eq!(color_thief::get_palette_indexed(&[0x30], 4, 2, &palette, 1, 2),
+               Err(color_thief::Error::InvalidIndex));
+    assert_eq!(color_thief::get_palette_indexed(&[0], 3, 2, &palette, 1, 2),
+               Err(color_thief::Error::InvalidBitDepth));
+    assert_eq!(color_thief::get_palette_indexed(&[0; 3], 4, 3, &palette, 1, 2),
+               Err(color_thief::Error::InvalidLength));
+    assert_eq!(color_thief::get_palette_indexed(&[0; 3], 4, 0, &palette, 1, 2),
+               Err(color_thief::Error::InvalidLength));
+}
+
+#[test]
+fn palette_distance() {
+    use color_thief::palette_distance;
+
+    let black = Color::new(0, 0, 0);
+    let white = Color::new(255, 255, 255);
+    let red = Color::new(255, 0, 0);
+    let blue = Color::new(0, 0, 255);
+
+    assert_eq!(palette_distance(&[], &[]), 0.0);
+    assert_eq!(palette_distance(&[red], &[]), 1.0);
+    assert_eq!(palette_distance(&[red, blue], &[blue, red]), 0.0);
+    assert!((palette_distance(&[black], &[white]) - 1.0).abs() < 1e-4);
+
+    // The extra color is matched with the nearest one.
+    assert_eq!(palette_distance(&[red, blue], &[red, blue, red]), 0.0);
+    let d = palette_distance(&[red], &[red, blue]);
+    assert_eq!(d, palette_distance(&[red, blue], &[red]));
+    assert!((d - 255.0 * 2f32.sqrt() / 2.0 / (255.0 * 3f32.sqrt())).abs() < 1e-4);
+
+    let img = image::open(path::Path::new("images/photo1.jpg")).unwrap();
+    let (buffer, color_type) = get_image_buffer(img);
+    let colors = color_thief::get_palette(&buffer, color_type, 10, 10).unwrap();
+    let colors2 = color_thief::get_palette(&buffer, color_type, 1, 10).unwrap();
+    let other = palette_distance(&colors, &[red, blue]);
+    assert!(palette_distance(&colors, &colors2) < other);
+}
+
+#[test]
+fn constrained_palette() {
+    let allowed = [Color::new(0, 0, 0), Color::new(255, 0, 0), Color::new(0, 0, 255)];
+
+    let mut buffer = [30, 20, 200].repeat(30);
+    buffer.extend([220, 40, 30].repeat(60));
+    buffer.extend([255, 255, 255].repeat(30));
+    let colors = color_thief::constrained_palette(&buffer, ColorFormat::Rgb, 1, &allowed).unwrap();
+    assert_eq!(colors, vec![(allowed[1], 20), (allowed[2], 10), (allowed[0], 0)]);
+
+    assert_eq!(color_thief::constrained_palette(&buffer, ColorFormat::Rgb, 1, &[]),
+               Err(color_thief::Error::EmptyPalette));
+    assert_eq!(color_thief::constrained_palette(&[], ColorFormat::Rgb, 1, &allowed),
+               Err(color_thief::Error::EmptyImage));
+}
+
+#[test]
+fn auto_quality() {
+    let img = image::open(path::Path::new("images/photo1.jpg")).unwrap();
+    let (buffer, color_type) = get_image_buffer(img);
+
+    let colors = color_thief::get_palette(&buffer, color_type, 0, 10).unwrap();
+    assert!(!colors.is_empty());
+
+    // Small images are sampled entirely.
+    let mut buffer = [255, 0, 0].repeat(10);
+    buffer.extend([0, 0, 255].repeat(5));
+    assert_eq!(color_thief::get_palette(&buffer, ColorFormat::Rgb, 0, 2),
+               color_thief::get_palette(&buffer, ColorFormat::Rgb, 1, 2));
+}
+
+#[test]
+fn color_format_from_str() {
+    assert_eq!("rgb".parse(), Ok(ColorFormat::Rgb));
+    assert_eq!("BGRA".parse(), Ok(ColorFormat::Bgra));
+    assert_eq!("gray_alpha".parse(), Ok(ColorFormat::GrayAlpha));
+    assert_eq!("ycbcr".parse(), Ok(ColorFormat::YCbCr));
+    assert_eq!("rgb8".parse::<ColorFormat>(), Err(color_thief::Error::UnknownColorFormat));
+    assert_eq!("".parse::<ColorFormat>(), Err(color_thief::Error::UnknownColorFormat));
+
+    assert_eq!(ColorFormat::Rgb.channels(), 3);
+    assert_eq!(ColorFormat::Bgrx.channels(), 4);
+    assert_eq!(ColorFormat::GrayAlpha.channels(), 2);
+}
+
+#[test]
+fn color_format_try_from_tag() {
+    use std::convert::TryFrom;
+
+    assert_eq!(ColorFormat::try_from(0), Ok(ColorFormat::Rgb));
+    assert_eq!(ColorFormat::try_from(5), Ok(ColorFormat::Bgra));
+    assert_eq!(ColorFormat::try_from(9), Ok(ColorFormat::GrayAlpha));
+    assert_eq!(ColorFormat::try_from(12), Ok(ColorFormat::YCbCr));
+    assert_eq!(ColorFormat::try_from(13), Err(color_thief::Error::UnknownColorFormat));
+    assert_eq!(ColorFormat::try_from(255), Err(color_thief::Error::UnknownColorFormat));
+}
+
+#[test]
+fn bytes_per_pixel() {
+    assert_eq!(ColorFormat::Rgb.bytes_per_pixel(), 3);
+    assert_eq!(ColorFormat::Argb.bytes_per_pixel(), 4);
+    assert_eq!(ColorFormat::Gray.bytes_per_pixel(), 1);
+
+    let buffer = [255, 0, 0, 255].repeat(10);
+    let format = ColorFormat::Rgba;
+    assert_eq!(buffer.len() % format.bytes_per_pixel(), 0);
+    assert!(color_thief::get_palette(&buffer, format, 1, 2).is_ok());
+    assert_eq!(color_thief::get_palette(&buffer[1..], format, 1, 2),
+               Err(color_thief::Error::InvalidLength));
+}
+
+#[test]
+fn invalid_arguments() {
+    let buffer = [0; 30];
+    assert_eq!(color_thief::get_palette(&buffer, ColorFormat::Rgb, 11, 10),
+               Err(color_thief::Error::InvalidQuality));
+    assert_eq!(color_thief::get_palette(&buffer, ColorFormat::Rgb, 10, 1),
+               Err(color_thief::Error::InvalidMaxColors));
+}
+
+#[test]
+fn stride() {
+    let img = image::open(path::Path::new("images/photo1.jpg")).unwrap();
+    let width = img.width() as usize;
+    let (buffer, color_type) = get_image_buffer(img);
+
+    let stride = width * 3 + 13;
+    let mut padded = Vec::new();
+    for row in buffer.chunks(width * 3) {
+        padded.extend_from_slice(row);
+        padded.extend_from_slice(&[255; 13]);
+    }
+
+    let colors = color_thief::get_palette(&buffer, color_type, 10, 10).unwrap();
+    let colors2 = color_thief::get_palette_with_stride(
+        &padded, color_type, width, stride, 10, 10).unwrap();
+    assert_eq!(colors, colors2);
+
+    assert_eq!(color_thief::get_palette_with_stride(&padded, color_type, width, 10, 10, 10),
+               Err(color_thief::Error::InvalidStride));
+}
+
+#[test]
+fn region() {
+    // Red 10x10 square with a blue 4x4 square in the middle.
+    let mut buffer = Vec::new();
+    for y in 0..10 {
+        for x in 0..10 {
+            if (3..7).contains(&x) && (3..7).contains(&y) {
+                buffer.extend_from_slice(&[0, 0, 255]);
+            } else {
+                buffer.extend_from_slice(&[255, 0, 0]);
+            }
+        }
+    }
+
+    let colors = color_thief::get_palette_region(
+        &buffer, ColorFormat::Rgb, 10, 10, Rect::new(3, 3, 4, 4), 1, 2).unwrap();
+    assert_eq!(colors[0], Color::new(4, 4, 252));
+
+    let colors = color_thief::get_palette_region(
+        &buffer, ColorFormat::Rgb, 10, 10, Rect::new(8, 0, 100, 100), 1, 2).unwrap();
+    assert_eq!(colors[0], Color::new(252, 4, 4));
+
+    assert_eq!(color_thief::get_palette_region(
+                   &buffer, ColorFormat::Rgb, 10, 10, Rect::new(10, 0, 5, 5), 1, 2),
+               Err(color_thief::Error::EmptyRegion));
+}
+
+#[test]
+fn histogram() {
+    let img1 = image::open(path::Path::new("images/photo1.jpg")).unwrap();
+    let (buffer1, color_type1) = get_image_buffer(img1);
+    let img2 = image::open(path::Path::new("images/iguana.png")).unwrap();
+    let (mixed, color_type2) = get_image_buffer(img2);
+
+    let mut histogram = color_thief::Histogram::new();
+    assert_eq!(histogram.quantize(10), Err(color_thief::Error::EmptyImage));
+
+    histogram.add_frame(&buffer1, color_type1, 10).unwrap();
+    assert_eq!(histogram.quantize(10).unwrap(),
+               color_thief::get_palette(&buffer1, color_type1, 10, 10).unwrap());
+
+    histogram.clear();
+    histogram.add_frame(&mixed, color_type2, 10).unwrap();
+    assert_eq!(histogram.quantize(10).unwrap(),
+               color_thief::get_palette(&mixed, color_type2, 10, 10).unwrap());
+}
+
+#[test]
+fn signal_bits() {
+    // Two colors that are the same in 5 bits, but differ in 6 bits.
+    let mut buffer = Vec::new();
+    for i in 0..1000 {
+        let c = if i < 500 { [200, 20, 20] } else { [205, 20, 20] };
+        buffer.extend_from_slice(&c);
+    }
+
+    let q = color_thief::Quantizer::new().quality(1).max_colors(2);
+    let colors = q.clone().build_palette(&buffer, ColorFormat::Rgb).unwrap();
+    assert_eq!(colors[0], Color::new(204, 20, 20));
+
+    let colors = q.clone().signal_bits(6).build_palette(&buffer, ColorFormat::Rgb).unwrap();
+    assert!(colors.contains(&Color::new(202, 22, 22)));
+    assert!(colors.contains(&Color::new(206, 22, 22)));
+
+    let mut colors = q.clone().signal_bits(8).build_palette(&buffer, ColorFormat::Rgb).unwrap();
+    colors.sort_by_key(|c| c.r);
+    assert_eq!(colors, vec![Color::new(200, 20, 20), Color::new(205, 20, 20)]);
+
+    assert_eq!(q.clone().signal_bits(0).build_palette(&buffer, ColorFormat::Rgb),
+               Err(color_thief::Error::InvalidSignalBits));
+    assert_eq!(q.clone().signal_bits(9).build_palette(&buffer, ColorFormat::Rgb),
+               Err(color_thief::Error::InvalidSignalBits));
+}
+
+#[test]
+fn sort_order() {
+    let img = image::open(path::Path::new("images/photo1.jpg")).unwrap();
+    let (buffer, color_type) = get_image_buffer(img);
+
+    let luma = |c: &Color| color_thief::color::relative_luminance(*c);
+
+    let q = color_thief::Quantizer::new();
+    let colors = q.clone().build_palette(&buffer, color_type).unwrap();
+
+    let asc = q.clone().sort_order(SortOrder::LuminanceAsc).build_palette(&buffer, color_type).unwrap();
+    assert_eq!(asc.len(), colors.len());
+    assert!(asc.windows(2).all(|w| luma(&w[0]) <= luma(&w[1])));
+
+    let mut desc = q.clone().sort_order(SortOrder::LuminanceDesc).build_palette(&buffer, color_type).unwrap();
+    desc.reverse();
+    assert_eq!(asc, desc);
+
+    // The same order as `Palette::sorted_by_luminance`.
+    let palette = q.clone().build(&buffer, color_type).unwrap();
+    assert_eq!(palette.sorted_by_luminance().colors(), asc.as_slice());
+
+    let hue = q.clone().sort_order(SortOrder::Hue).build_palette(&buffer, color_type).unwrap();
+    assert_eq!(hue[0], Color::new(54, 37, 28));
+    assert_eq!(hue[1], Color::new(212, 80, 7));
+}
+
+#[test]
+fn hsl_hsv() {
+    use color_thief::color::{to_hsl, to_hsv};
+
+    assert_eq!(to_hsl(&Color::new(255, 0, 0)), (0.0, 1.0, 0.5));
+    assert_eq!(to_hsl(&Color::new(0, 255, 0)), (120.0, 1.0, 0.5));
+    assert_eq!(to_hsl(&Color::new(0, 0, 255)), (240.0, 1.0, 0.5));
+    assert_eq!(to_hsl(&Color::new(255, 255, 255)), (0.0, 0.0, 1.0));
+    assert_eq!(to_hsl(&Color::new(0, 0, 0)), (0.0, 0.0, 0.0));
+
+    let (h, s, l) = to_hsl(&Color::new(255, 128, 128));
+    assert_eq!(h, 0.0);
+    assert_eq!(s, 1.0);
+    assert!((l - 0.751).abs() < 0.001);
+
+    assert_eq!(to_hsv(&Color::new(255, 0, 255)), (300.0, 1.0, 1.0));
+    assert_eq!(to_hsv(&Color::new(0, 0, 0)), (0.0, 0.0, 0.0));
+
+    let (h, s, v) = to_hsv(&Color::new(0, 128, 64));
+    assert!((h - 150.0).abs() < 0.5);
+    assert_eq!(s, 1.0);
+    assert!((v - 0.502).abs() < 0.001);
+}
+
+#[test]
+fn nearest_color() {
+    let palette = [Color::new(0, 0, 0), Color::new(255, 255, 255), Color::new(200, 0, 0)];
+
+    assert_eq!(color_thief::nearest_color(&palette, Color::new(10, 20, 30)), Some(0));
+    assert_eq!(color_thief::nearest_color(&palette, Color::new(220, 220, 220)), Some(1));
+    assert_eq!(color_thief::nearest_color(&palette, Color::new(150, 20, 20)), Some(2));
+    assert_eq!(color_thief::nearest_color(&[], Color::new(150, 20, 20)), None);
+}
+
+#[test]
+fn indexed() {
+    let mut buffer = Vec::new();
+    for i in 0..1000 {
+        let c = if i < 700 { [200, 20, 20, 255] } else { [20, 20, 200, 255] };
+        buffer.extend_from_slice(&c);
+    }
+    // A transparent pixel.
+    buffer.extend_from_slice(&[190, 30, 30, 0]);
+
+    let (palette, indices) = color_thief::get_indexed(&buffer, ColorFormat::Rgba, 1, 2).unwrap();
+    assert_eq!(indices.len(), 1001);
+
+    let red = palette.iter().position(|c| *c == Color::new(204, 20, 20)).unwrap() as u8;
+    let blue = palette.iter().position(|c| *c == Color::new(20, 20, 204)).unwrap() as u8;
+    assert!(indices[..700].iter().all(|i| *i == red));
+    assert!(indices[700..1000].iter().all(|i| *i == blue));
+    assert_eq!(indices[1000], red);
+}
+
+#[test]
+fn compat_mode_py() {
+    let img = image::open(path::Path::new("images/photo1.jpg")).unwrap();
+    let (buffer, color_type) = get_image_buffer(img);
+    let colors = color_thief::Quantizer::new()
+        .compat_mode(color_thief::CompatMode::ColorThiefPy)
+        .build_palette(&buffer, color_type)
+        .unwrap();
+
+    // Regression values, not produced by colorthief-py itself.
+    // They differ from the color-thief JS values in `image1`,
+    // since colorthief-py samples pixels and orders boxes differently.
+    assert_eq!(colors, vec![
+        Color::new( 53,  37,  28),
+        Color::new(214, 194, 134),
+        Color::new(110, 205, 223),
+        Color::new(129, 122,  58),
+        Color::new( 42, 125, 148),
+        Color::new(156, 175, 120),
+        Color::new(132, 122, 109),
+        Color::new(167, 199, 220),
+        Color::new(210,  77,   7),
+    ]);
+}
+
+#[test]
+fn large_image() {
+    // Sums of channel values don't fit into `i32`.
+    let buffer = vec![200; 12_000_000];
+    let counts = color_thief::get_palette_with_counts(&buffer, ColorFormat::Gray, 1, 2).unwrap();
+    assert_eq!(counts[0], (Color::new(204, 204, 204), 12_000_000));
+}
+
+#[test]
+fn fraction() {
+    let img = image::open(path::Path::new("images/photo1.jpg")).unwrap();
+    let (buffer, color_type) = get_image_buffer(img);
+
+    // Sampling all pixels is the same as a step of 1 pixel.
+    // `CompatMode::ColorThiefPy` doesn't move the most populated color first.
+    let options = color_thief::Options {
+        compat_mode: color_thief::CompatMode::ColorThiefPy,
+        ..Default::default()
+    };
+    assert_eq!(sorted(color_thief::get_palette_with_fraction(&buffer, color_type, 1.0, 10).unwrap()),
+               sorted(color_thief::get_palette_with_options(&buffer, color_type, 1, 10, &options).unwrap()));
+
+    // At least one pixel is sampled.
+    let buffer = [200, 20, 20];
+    assert_eq!(color_thief::get_palette_with_fraction(&buffer, ColorFormat::Rgb, 0.001, 2).unwrap()[0],
+               Color::new(204, 20, 20));
+
+    for fraction in [0.0, -0.5, 1.5, f32::NAN] {
+        assert_eq!(color_thief::get_palette_with_fraction(&buffer, ColorFormat::Rgb, fraction, 2),
+                   Err(color_thief::Error::InvalidFraction));
+    }
+}
+
+#[test]
+fn random_sampling() {
+    // Stripes with the same period as the sampling step.
+    let mut buffer = Vec::new();
+    for i in 0..3000 {
+        let c = if i % 3 == 0 { [200, 20, 20] } else { [20, 20, 200] };
+        buffer.extend_from_slice(&c);
+    }
+
+    let quantizer = color_thief::Quantizer::new().quality(1).max_colors(2);
+    let colors = quantizer.clone().build_palette(&buffer, ColorFormat::Rgb).unwrap();
+    assert_eq!(colors[0], Color::new(204, 20, 20));
+    assert!(!colors.contains(&Color::new(20, 20, 204)));
+
+    let quantizer = quantizer.sampling(Sampling::Random { seed: 42 });
+    let colors = quantizer.build_palette(&buffer, ColorFormat::Rgb).unwrap();
+    assert_eq!(colors, vec![Color::new(20, 20, 204), Color::new(204, 20, 20)]);
+    assert_eq!(quantizer.build_palette(&buffer, ColorFormat::Rgb).unwrap(), colors);
+}
+
+#[test]
+fn downscale_sampling() {
+    // A checkerboard of 1px red and blue cells.
+    let (width, height) = (200, 200);
+    let mut buffer = Vec::new();
+    for y in 0..height {
+        for x in 0..width {
+            let c = if (x + y) % 2 == 0 { [200, 0, 0] } else { [0, 0, 200] };
+            buffer.extend_from_slice(&c);
+        }
+    }
+
+    // Every 2x2 block is averaged into purple.
+    let quantizer = color_thief::Quantizer::new().max_colors(2).sampling(Sampling::Downscale { max_dim: 100 });
+    let colors = quantizer.clone().build_palette_with_size(&buffer, ColorFormat::Rgb, width, height).unwrap();
+    assert_eq!(colors[0], Color::new(100, 4, 100));
+
+    // Without dimensions the image would be a single row.
+    assert_eq!(quantizer.build_palette(&buffer, ColorFormat::Rgb),
+               Err(color_thief::Error::MissingDimensions));
+    let options = color_thief::Options { sampling: Sampling::Downscale { max_dim: 100 }, ..Default::default() };
+    assert_eq!(color_thief::get_palette_with_options(&buffer, ColorFormat::Rgb, 10, 2, &options),
+               Err(color_thief::Error::MissingDimensions));
+
+    let quantizer = quantizer.sampling(Sampling::Downscale { max_dim: 0 });
+    assert_eq!(quantizer.build_palette_with_size(&buffer, ColorFormat::Rgb, width, height),
+               Err(color_thief::Error::InvalidMaxDim));
+
+    let img = image::open(path::Path::new("images/photo1.jpg")).unwrap();
+    let (width, height) = (img.width() as usize, img.height() as usize);
+    let (buffer, color_type) = get_image_buffer(img);
+    let quantizer = color_thief::Quantizer::new().sampling(Sampling::Downscale { max_dim: 100 });
+    let colors = quantizer.build_palette_with_size(&buffer, color_type, width, height).unwrap();
+    assert!(colors.len() > 5);
+}
+
+#[test]
+fn deterministic_parallel() {
+    use color_thief::{AlphaMode, CompatMode, Quantizer};
+
+    // `build_palette_with_progress` is always sequential,
+    // while `build_palette` runs in parallel with the `rayon` feature.
+    // Every pixel is sampled, so a histogram is split into multiple chunks.
+    for path in &["images/photo1.jpg", "images/iguana.png"] {
+        let img = image::open(path::Path::new(path)).unwrap();
+        let (buffer, color_type) = get_image_buffer(img);
+
+        let quantizer = Quantizer::new().compat_mode(CompatMode::ColorThiefPy).quality(1).max_colors(16);
+        let quantizers = [
+            quantizer.clone(),
+            quantizer.clone().saturation_weight(0.7),
+            quantizer.clone().alpha_mode(AlphaMode::Weighted),
+            quantizer.clone().linear_average(true).refine_iterations(3),
+        ];
+
+        for quantizer in &quantizers {
+            let parallel = quantizer.clone().build_palette(&buffer, color_type).unwrap();
+            let serial = quantizer.clone().build_palette_with_progress(&buffer, color_type, &mut |_| true).unwrap();
+            assert_eq!(parallel, serial);
+        }
+    }
+}
+
+#[test]
+fn linear_average() {
+    let img = image::open(path::Path::new("images/photo1.jpg")).unwrap();
+    let (buffer, color_type) = get_image_buffer(img);
+
+    let quantizer = color_thief::Quantizer::new();
+    let colors = quantizer.build_palette(&buffer, color_type).unwrap();
+    let linear = quantizer.linear_average(true).build_palette(&buffer, color_type).unwrap();
+
+    // Boxes are the same, but averaging of the linear light produces lighter colors.
+    assert_eq!(colors.len(), linear.len());
+    assert_ne!(colors, linear);
+    for (c1, c2) in colors.iter().zip(linear.iter()) {
+        assert!(c1.r <= c2.r && c1.g <= c2.g && c1.b <= c2.b);
+    }
+
+    assert_eq!(color_thief::color::srgb_to_linear(0), 0.0);
+    assert_eq!(color_thief::color::srgb_to_linear(255), 1.0);
+    for c in 0..=255 {
+        assert_eq!(color_thief::color::linear_to_srgb(color_thief::color::srgb_to_linear(c)), c);
+    }
+}
+
+#[test]
+fn saturation_weight() {
+    let mut buffer = Vec::new();
+    for i in 0..1000 {
+        let c = match i / 100 {
+            0 => [200, 20, 20],
+            1 => [20, 200, 20],
+            n => { let v = 40 + n as u8 * 20; [v, v, v] }
+        };
+        buffer.extend_from_slice(&c);
+    }
+
+    // Green is mixed with gray by default.
+    let quantizer = color_thief::Quantizer::new().quality(1).max_colors(4);
+    assert_eq!(quantizer.clone().build_palette(&buffer, ColorFormat::Rgb).unwrap(), vec![
+        Color::new(172, 172, 172),
+        Color::new( 52, 144,  52),
+        Color::new(204,  20,  20),
+        Color::new(100, 100, 100),
+    ]);
+
+    assert_eq!(quantizer.clone().saturation_weight(20.0).build_palette(&buffer, ColorFormat::Rgb).unwrap(), vec![
+        Color::new(198,  24,  24),
+        Color::new( 20, 204,  20),
+        Color::new(172, 172, 172),
+        Color::new( 84,  84,  84),
+    ]);
+
+    // Counts are in pixels, a red pixel with a 0.9 saturation counts as 1.9 pixels.
+    let mut mixed = [200, 20, 20].repeat(300);
+    mixed.extend([100, 100, 100].repeat(300));
+    let palette = quantizer.clone().max_colors(2).saturation_weight(1.0).build(&mixed, ColorFormat::Rgb).unwrap();
+    assert_eq!(palette.counts(), &[190, 100]);
+
+    assert!(quantizer.clone().saturation_weight(1000.0).build_palette(&buffer, ColorFormat::Rgb).is_ok());
+    for weight in [-1.0, 1000.5, f32::MAX, f32::NAN, f32::INFINITY] {
+        assert_eq!(quantizer.clone().saturation_weight(weight).build_palette(&buffer, ColorFormat::Rgb),
+                   Err(color_thief::Error::InvalidSaturationWeight));
+    }
+}
+
+#[test]
+fn swatches() {
+    let mut buffer = Vec::new();
+    for i in 0..900 {
+        let c = match i / 300 {
+            0 => [230, 20, 20],
+            1 => [40, 60, 50],
+            _ => [200, 180, 190],
+        };
+        buffer.extend_from_slice(&c);
+    }
+
+    let swatches = color_thief::swatches(&buffer, ColorFormat::Rgb, 1).unwrap();
+    assert_eq!(swatches, color_thief::Swatches {
+        vibrant: Some(Color::new(228, 20, 20)),
+        light_muted: Some(Color::new(204, 180, 188)),
+        dark_muted: Some(Color::new(44, 60, 52)),
+        ..Default::default()
+    });
+
+    assert_eq!(color_thief::swatches(&buffer, ColorFormat::Rgb, 11),
+               Err(color_thief::Error::InvalidQuality));
+}
+
+#[test]
+fn contrast() {
+    use color_thief::color::{best_text_color, contrast_ratio};
+
+    let black = Color::new(0, 0, 0);
+    let white = Color::new(255, 255, 255);
+    let ratio = |a, b| (contrast_ratio(a, b) * 100.0).round() / 100.0;
+    assert_eq!(ratio(black, white), 21.0);
+    assert_eq!(ratio(white, black), 21.0);
+    assert_eq!(ratio(white, white), 1.0);
+    assert_eq!(ratio(Color::new(118, 118, 118), white), 4.54);
+
+    assert_eq!(best_text_color(white), black);
+    assert_eq!(best_text_color(Color::new(255, 255, 0)), black);
+    assert_eq!(best_text_color(black), white);
+    assert_eq!(best_text_color(Color::new(0, 0, 128)), white);
+}
+
+#[test]
+fn hex() {
+    use color_thief::color::{from_hex, to_hex};
+
+    assert_eq!(to_hex(&Color::new(0, 128, 255)), "#0080ff");
+    assert_eq!(from_hex("#0080ff"), Some(Color::new(0, 128, 255)));
+    assert_eq!(from_hex("0080FF"), Some(Color::new(0, 128, 255)));
+    assert_eq!(from_hex("#f80"), Some(Color::new(255, 136, 0)));
+
+    for text in ["", "#", "#ff", "#ff00", "#ff00ff00", "#gg0000", "#+f+f+f", "#ff00fé"] {
+        assert_eq!(from_hex(text), None);
+    }
+}
+
+#[test]
+fn background_color() {
+    // A red square on a blue background.
+    let (width, height) = (100, 80);
+    let mut buffer = Vec::new();
+    for y in 0..height {
+        for x in 0..width {
+            let c = if x > 10 && x < 90 && y > 10 && y < 70 { [200, 20, 20] } else { [20, 20, 200] };
+            buffer.extend_from_slice(&c);
+        }
+    }
+
+    assert_eq!(color_thief::get_background_color(&buffer, ColorFormat::Rgb, width, height, 1).unwrap(),
+               Color::new(20, 20, 204));
+
+    // The border is the whole image.
+    let buffer = [200, 20, 20, 200, 20, 20];
+    assert_eq!(color_thief::get_background_color(&buffer, ColorFormat::Rgb, 2, 1, 1).unwrap(),
+               Color::new(204, 20, 20));
+
+    assert_eq!(color_thief::get_background_color(&buffer, ColorFormat::Rgb, 0, 1, 1),
+               Err(color_thief::Error::EmptyImage));
+    assert_eq!(color_thief::get_background_color(&buffer, ColorFormat::Rgb, 2, 1, 11),
+               Err(color_thief::Error::InvalidQuality));
+}
+
+#[test]
+fn min_distance() {
+    fn min_distance(colors: &[Color]) -> f32 {
+        let mut min = f32::MAX;
+        for (i, a) in colors.iter().enumerate() {
+            for b in &colors[i + 1..] {
+                let d = (a.r as f32 - b.r as f32).powi(2)
+                      + (a.g as f32 - b.g as f32).powi(2)
+                      + (a.b as f32 - b.b as f32).powi(2);
+                min = min.min(d.sqrt());
+            }
+        }
+        min
+    }
+
+    let img = image::open(path::Path::new("images/photo1.jpg")).unwrap();
+    let (buffer, color_type) = get_image_buffer(img);
+    let quantizer = color_thief::Quantizer::new();
+
+    let colors = quantizer.clone().build_palette(&buffer, color_type).unwrap();
+    assert_eq!(colors.len(), 9);
+    assert!(min_distance(&colors) < 60.0);
+
+    let merged = quantizer.clone().min_distance(60).build_palette(&buffer, color_type).unwrap();
+    assert_eq!(merged.len(), 6);
+    assert!(min_distance(&merged) >= 60.0);
+    assert_eq!(merged[1], colors[1]);
+
+    let refilled = quantizer.clone().min_distance(60).refill(true).build_palette(&buffer, color_type).unwrap();
+    assert_eq!(refilled.len(), 9);
+    assert!(min_distance(&refilled) >= 60.0);
+
+    assert_eq!(quantizer.clone().min_distance(60).refill(true).build_palette(&buffer, color_type).unwrap(),
+               refilled);
+}
+
+#[test]
+fn refine_iterations() {
+    // A total squared distance of pixels to the closest palette color.
+    fn error(buffer: &[u8], palette: &[Color]) -> u64 {
+        buffer.chunks(3).map(|c| {
+            let c = Color::new(c[0], c[1], c[2]);
+            let i = color_thief::nearest_color(palette, c).unwrap();
+            let p = palette[i];
+            let d = |a: u8, b: u8| (a as i64 - b as i64).pow(2) as u64;
+            d(c.r, p.r) + d(c.g, p.g) + d(c.b, p.b)
+        }).sum()
+    }
+
+    let img = image::open(path::Path::new("images/photo1.jpg")).unwrap();
+    let (buffer, color_type) = get_image_buffer(img);
+    let quantizer = color_thief::Quantizer::new();
+
+    let colors = quantizer.clone().build_palette(&buffer, color_type).unwrap();
+    let refined = quantizer.clone().refine_iterations(5).build_palette(&buffer, color_type).unwrap();
+    assert_eq!(colors.len(), refined.len());
+    assert_ne!(colors, refined);
+    assert!(error(&buffer, &refined) < error(&buffer, &colors));
+
+    assert_eq!(quantizer.clone().refine_iterations(0).build_palette(&buffer, color_type).unwrap(), colors);
+}
+
+#[test]
+fn composite_over() {
+    let buffer = [255, 0, 0, 100].repeat(40);
+    let quantizer = color_thief::Quantizer::new().quality(1).max_colors(2);
+    assert_eq!(quantizer.clone().build_palette(&buffer, ColorFormat::Rgba),
+               Err(color_thief::Error::NoVisiblePixels));
+
+    let white = Color::new(255, 255, 255);
+    let colors = quantizer.clone().composite_over(Some(white)).build_palette(&buffer, ColorFormat::Rgba).unwrap();
+    assert_eq!(colors[0], Color::new(252, 156, 156));
+
+    // Fully transparent pixels become the background.
+    let mut buffer = [0, 0, 0, 0].repeat(40);
+    buffer.extend([0, 0, 255, 255].repeat(20));
+    let green = Color::new(0, 255, 0);
+    let colors = quantizer.clone().composite_over(Some(green)).build_palette(&buffer, ColorFormat::Rgba).unwrap();
+    assert_eq!(colors[0], Color::new(4, 252, 4));
+}
+
+#[test]
+fn snap_exact() {
+    let red = Color::new(255, 0, 0);
+    let blue = Color::new(0, 0, 255);
+    let orange = Color::new(250, 120, 10);
+    let mut buffer = [255, 0, 0].repeat(300);
+    buffer.extend([0, 0, 255].repeat(200));
+    buffer.extend([250, 120, 10].repeat(100));
+
+    let quantizer = color_thief::Quantizer::new().max_colors(4);
+    let colors = quantizer.clone().build_palette(&buffer, ColorFormat::Rgb).unwrap();
+    assert!(!colors.contains(&red));
+
+    let snapped = quantizer.clone().snap_exact(true).build_palette(&buffer, ColorFormat::Rgb).unwrap();
+    assert_eq!(snapped.len(), colors.len());
+    assert!(snapped.contains(&red) && snapped.contains(&blue) && snapped.contains(&orange));
+
+    // Cells with different colors are not snapped.
+    let mut buffer = [255, 0, 0].repeat(100);
+    buffer.extend([250, 2, 2].repeat(100));
+    let snapped = quantizer.clone().snap_exact(true).build_palette(&buffer, ColorFormat::Rgb).unwrap();
+    let colors = quantizer.clone().build_palette(&buffer, ColorFormat::Rgb).unwrap();
+    assert_eq!(snapped, colors);
+
+    // Photos are mostly left intact.
+    let img = image::open(path::Path::new("images/photo1.jpg")).unwrap();
+    let (buffer, color_type) = get_image_buffer(img);
+    let quantizer = color_thief::Quantizer::new();
+    let colors = quantizer.clone().build_palette(&buffer, color_type).unwrap();
+    let snapped = quantizer.clone().snap_exact(true).build_palette(&buffer, color_type).unwrap();
+    assert_eq!(snapped.len(), colors.len());
+    for (a, b) in colors.iter().zip(&snapped) {
+        assert!((a.r as i32 - b.r as i32).abs() <= 8);
+        assert!((a.g as i32 - b.g as i32).abs() <= 8);
+        assert!((a.b as i32 - b.b as i32).abs() <= 8);
+    }
+}
+
+#[test]
+fn octree() {
+    let mut buffer = Vec::new();
+    for i in 0..900 {
+        let c = match i / 300 {
+            0 => [200, 20, 20],
+            1 => [20, 200, 20],
+            _ => [20, 20, 200],
+        };
+        buffer.extend_from_slice(&c);
+    }
+
+    let quantizer = color_thief::Quantizer::new().quality(1).algorithm(Algorithm::Octree);
+    let colors = quantizer.clone().max_colors(5).build_palette(&buffer, ColorFormat::Rgb).unwrap();
+    assert_eq!(colors, vec![
+        Color::new( 20,  20, 204),
+        Color::new( 20, 204,  20),
+        Color::new(204,  20,  20),
+    ]);
+
+    let img = image::open(path::Path::new("images/photo1.jpg")).unwrap();
+    let (buffer, color_type) = get_image_buffer(img);
+    assert_eq!(quantizer.clone().max_colors(5).build_palette(&buffer, color_type).unwrap(), vec![
+        Color::new(100, 201, 221),
+        Color::new( 60,  49,  40),
+        Color::new(185, 205, 195),
+        Color::new(187, 176,  85),
+        Color::new(171, 106,  49),
+    ]);
+
+    for max_colors in [2, 10, 32] {
+        let colors = quantizer.clone().max_colors(max_colors).build_palette(&buffer, color_type).unwrap();
+        assert!(colors.len() > 1 && colors.len() <= max_colors as usize);
+    }
+}
+
+#[test]
+fn iterations_and_fraction() {
+    let img = image::open(path::Path::new("images/photo1.jpg")).unwrap();
+    let (buffer, color_type) = get_image_buffer(img);
+    let quantizer = color_thief::Quantizer::new().max_colors(20);
+
+    let colors = quantizer.clone().build_palette(&buffer, color_type).unwrap();
+    assert_eq!(quantizer.clone().max_iterations(1000).population_fraction(0.75)
+                   .build_palette(&buffer, color_type).unwrap(), colors);
+
+    // Each phase makes only a few cuts.
+    let limited = quantizer.clone().max_iterations(3).build_palette(&buffer, color_type).unwrap();
+    assert_eq!(limited.len(), 7);
+
+    let by_volume = quantizer.clone().population_fraction(0.0).build_palette(&buffer, color_type).unwrap();
+    assert_ne!(by_volume, colors);
+
+    for fraction in [-0.1, 1.1, f32::NAN] {
+        assert_eq!(quantizer.clone().population_fraction(fraction).build_palette(&buffer, color_type),
+                   Err(color_thief::Error::InvalidPopulationFraction));
+    }
+}
+
+#[test]
+fn exact_colors() {
+    let img = image::open(path::Path::new("images/photo1.jpg")).unwrap();
+    let (buffer, color_type) = get_image_buffer(img);
+    let quantizer = color_thief::Quantizer::new().exact_colors(true);
+
+    // Photos have enough colors, even though the median cut returns one color less.
+    assert_eq!(quantizer.clone().max_colors(10).build_palette(&buffer, color_type).unwrap().len(), 9);
+    assert_eq!(quantizer.clone().max_colors(10).algorithm(Algorithm::Octree)
+                   .build_palette(&buffer, color_type).unwrap().len(), 8);
+
+    // Only 3 distinct colors.
+    let mut flat = [255, 0, 0].repeat(100);
+    flat.extend([0, 255, 0].repeat(50));
+    flat.extend([0, 0, 255].repeat(20));
+    assert_eq!(quantizer.clone().max_colors(5).build_palette(&flat, ColorFormat::Rgb),
+               Err(color_thief::Error::InsufficientColors { found: 3, requested: 5 }));
+    assert_eq!(quantizer.clone().max_colors(3).build_palette(&flat, ColorFormat::Rgb).unwrap().len(), 3);
+    // Colors in the same histogram cell are not distinct.
+    flat.extend([1, 1, 254].repeat(20));
+    assert_eq!(quantizer.clone().max_colors(4).build_palette(&flat, ColorFormat::Rgb),
+               Err(color_thief::Error::InsufficientColors { found: 3, requested: 4 }));
+
+    let error = color_thief::Error::InsufficientColors { found: 9, requested: 10 };
+    assert_eq!(error.to_string(), "found 9 colors out of 10 requested");
+}
+
+#[test]
+fn typed_pixels() {
+    let img = image::open(path::Path::new("images/iguana.png")).unwrap();
+    let (buffer, color_type) = get_image_buffer(img);
+    assert_eq!(color_type, ColorFormat::Rgba);
+
+    let pixels: Vec<_> = buffer.chunks(4).map(|c| color_thief::RGBA8::new(c[0], c[1], c[2], c[3])).collect();
+    assert_eq!(color_thief::get_palette_rgba(&pixels, 10, 10),
+               color_thief::get_palette(&buffer, ColorFormat::Rgba, 10, 10));
+
+    let buffer: Vec<_> = buffer.chunks(4).flat_map(|c| [c[0], c[1], c[2]]).collect();
+    let pixels: Vec<_> = buffer.chunks(3).map(|c| Color::new(c[0], c[1], c[2])).collect();
+    assert_eq!(color_thief::get_palette_rgb(&pixels, 10, 10),
+               color_thief::get_palette(&buffer, ColorFormat::Rgb, 10, 10));
+}
+
+#[test]
+fn palette_from_iter() {
+    let img = image::open(path::Path::new("images/photo1.jpg")).unwrap();
+    let (buffer, color_type) = get_image_buffer(img);
+
+    // `CompatMode::ColorThiefPy` uses a step in pixels as well.
+    let options = color_thief::Options {
+        compat_mode: color_thief::CompatMode::ColorThiefPy,
+        ..Default::default()
+    };
+    let colors = buffer.chunks(3).map(|c| Color::new(c[0], c[1], c[2]));
+    assert_eq!(sorted(color_thief::get_palette_from_iter(colors.clone(), 5, 10).unwrap()),
+               sorted(color_thief::get_palette_with_options(&buffer, color_type, 5, 10, &options).unwrap()));
+
+    // The zero quality needs an exact length, or every color is sampled.
+    let colors: Vec<_> = colors.take(50_000).collect();
+    assert_eq!(color_thief::get_palette_from_iter(colors.clone(), 0, 10),
+               color_thief::get_palette_from_iter(colors.clone(), 5, 10));
+    assert_eq!(color_thief::get_palette_from_iter(colors.iter().cloned().filter(|_| true), 0, 10),
+               color_thief::get_palette_from_iter(colors.clone(), 1, 10));
+    assert_ne!(color_thief::get_palette_from_iter(colors.clone(), 1, 10),
+               color_thief::get_palette_from_iter(colors.clone(), 5, 10));
+
+    assert_eq!(color_thief::get_palette_from_iter(Vec::new(), 5, 10),
+               Err(color_thief::Error::EmptyImage));
+    assert_eq!(color_thief::get_palette_from_iter((0..100).map(|_| Color::new(255, 255, 255)), 1, 10),
+               Ok(vec![Color::new(255, 255, 255)]));
+}
+
+#[test]
+fn mask() {
+    let mut buffer = Vec::new();
+    let mut mask = Vec::new();
+    for i in 0..1000 {
+        let c = if i < 500 { [200, 20, 20] } else { [20, 20, 200] };
+        buffer.extend_from_slice(&c);
+        mask.push(if i < 500 { 0 } else { 1 });
+    }
+
+    let colors = color_thief::get_palette_with_mask(&buffer, ColorFormat::Rgb, &mask, 1, 2).unwrap();
+    assert_eq!(colors[0], Color::new(20, 20, 204));
+    assert!(!colors.contains(&Color::new(204, 20, 20)));
+
+    let mask = vec![0; 1000];
+    assert_eq!(color_thief::get_palette_with_mask(&buffer, ColorFormat::Rgb, &mask, 1, 2),
+               Err(color_thief::Error::EmptyImage));
+
+    assert_eq!(color_thief::get_palette_with_mask(&buffer, ColorFormat::Rgb, &mask[1..], 1, 2),
+               Err(color_thief::Error::InvalidMask));
+}
+
+#[test]
+fn spatial_weight() {
+    // Red and green squares on a gray background.
+    let (width, height) = (100, 100);
+    let mut buffer = Vec::new();
+    for y in 0..height {
+        for x in 0..width {
+            let c = match (x, y) {
+                (35..=49, 35..=64) => [200, 20, 20],
+                (50..=64, 35..=64) => [20, 200, 20],
+                _ => { let v = 40 + (y / 10) as u8 * 20; [v, v, v] }
+            };
+            buffer.extend_from_slice(&c);
+        }
+    }
+
+    // Red is mixed with gray by default.
+    let quantizer = color_thief::Quantizer::new().quality(1).max_colors(4);
+    let colors = quantizer.clone().build_palette_with_size(&buffer, ColorFormat::Rgb, width, height).unwrap();
+    assert!(colors.contains(&Color::new(121, 64, 64)));
+    assert!(!colors.contains(&Color::new(204, 20, 20)));
+
+    let colors = quantizer.clone()
+        .spatial_weight(SpatialWeight::Center)
+        .build_palette_with_size(&buffer, ColorFormat::Rgb, width, height)
+        .unwrap();
+    assert!(colors.contains(&Color::new(204, 20, 20)));
+
+    // The center of a buffer without dimensions is meaningless.
+    assert_eq!(quantizer.clone().spatial_weight(SpatialWeight::Center).build_palette(&buffer, ColorFormat::Rgb),
+               Err(color_thief::Error::MissingDimensions));
+}
+
+#[test]
+fn precomputed_histogram() {
+    let img = image::open(path::Path::new("images/iguana.png")).unwrap();
+    let (buffer, color_type) = get_image_buffer(img);
+
+    let mut histogram = color_thief::Histogram::new();
+    assert_eq!(histogram.bounds(), None);
+    histogram.add_frame(&buffer, color_type, 10).unwrap();
+    let colors = color_thief::quantize_histogram(&histogram, 10).unwrap();
+    assert_eq!(colors, color_thief::get_palette(&buffer, color_type, 10, 10).unwrap());
+
+    let counts = histogram.counts().to_vec();
+    assert_eq!(counts.len(), 1 << 15);
+    let restored = color_thief::Histogram::from_counts(counts.clone(), *histogram.options()).unwrap();
+    assert_eq!(restored.bounds(), histogram.bounds());
+    assert_eq!(color_thief::quantize_histogram(&restored, 10).unwrap(), colors);
+
+    let options = color_thief::Options { signal_bits: 4, ..Default::default() };
+    assert_eq!(color_thief::Histogram::from_counts(counts, options).err(),
+               Some(color_thief::Error::InvalidHistogram));
+
+    let mut counts = vec![0; 1 << 15];
+    counts[(1 << 10) | (2 << 5) | 3] = 10;
+    let histogram = color_thief::Histogram::from_counts(counts, Default::default()).unwrap();
+    assert_eq!(histogram.bounds(), Some(([1, 2, 3], [1, 2, 3])));
+    assert_eq!(color_thief::quantize_histogram(&histogram, 2).unwrap()[0], Color::new(12, 20, 28));
+}
+
+#[test]
+fn palette_with_alpha() {
+    let mut pixels = Vec::new();
+    for i in 0..300 {
+        let (color, alpha) = match i {
+            0..=99 => ([200, 0, 0], 200),
+            100..=199 => ([200, 0, 0], 140),
+            _ => ([0, 0, 200], 255),
+        };
+        pixels.extend_from_slice(&color);
+        pixels.push(alpha);
+    }
+
+    let quantizer = color_thief::Quantizer::new().quality(1).max_colors(3);
+    let colors = quantizer.build_palette_with_alpha(&pixels, ColorFormat::Rgba).unwrap();
+    assert!(colors.contains(&color_thief::RGBA8::new(204, 4, 4, 170)));
+    assert!(colors.contains(&color_thief::RGBA8::new(4, 4, 204, 255)));
+
+    let rgb: Vec<u8> = pixels.chunks(4).flat_map(|p| p[..3].to_vec()).collect();
+    let colors = quantizer.build_palette_with_alpha(&rgb, ColorFormat::Rgb).unwrap();
+    assert!(colors.iter().all(|c| c.a == 255));
+}
+
+#[test]
+fn palette_type() {
+    let img = image::open(path::Path::new("images/iguana.png")).unwrap();
+    let (buffer, color_type) = get_image_buffer(img);
+
+    let palette = color_thief::palette(&buffer, color_type, 10, 10).unwrap();
+    let colors = color_thief::get_palette(&buffer, color_type, 10, 10).unwrap();
+    assert_eq!(palette.len(), colors.len());
+    assert_eq!(palette.colors(), &colors[..]);
+    assert_eq!(palette.iter().cloned().collect::<Vec<_>>(), colors);
+    assert_eq!(palette.dominant(), colors[0]);
+    assert_eq!(color_thief::palette(&buffer, color_type, 10, 5).unwrap().dominant(),
+               color_thief::get_dominant_color(&buffer, color_type, 10).unwrap());
+
+    let counts: Vec<_> = color_thief::get_palette_with_counts(&buffer, color_type, 10, 10).unwrap()
+        .iter().map(|c| c.1).collect();
+    assert_eq!(palette.counts(), &counts[..]);
+
+    let sorted = palette.sorted_by_luminance();
+    assert_eq!(sorted.len(), palette.len());
+    let luminance: Vec<_> = sorted.iter().map(|&c| color_thief::color::relative_luminance(c)).collect();
+    assert!(luminance.windows(2).all(|w| w[0] <= w[1]));
+
+    let quantizer = color_thief::Quantizer::new();
+    assert_eq!(Vec::from(quantizer.build(&buffer, color_type).unwrap()),
+               quantizer.build_palette(&buffer, color_type).unwrap());
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn palette_deserialize() {
+    use serde::de::value::{Error, MapDeserializer, SeqDeserializer};
+    use serde::de::{Deserializer, IntoDeserializer, Visitor};
+    use serde::Deserialize;
+
+    // A minimal self-describing value, since there is no serde format in dev-dependencies.
+    enum Value {
+        Int(u64),
+        Seq(Vec<Value>),
+    }
+
+    impl<'de> Deserializer<'de> for Value {
+        type Error = Error;
+
+        fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+            match self {
+                Value::Int(v) => visitor.visit_u64(v),
+                Value::Seq(v) => visitor.visit_seq(SeqDeserializer::new(v.into_iter())),
+            }
+        }
+
+        serde::forward_to_deserialize_any! {
+            bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+            bytes byte_buf option unit unit_struct newtype_struct seq tuple
+            tuple_struct map struct enum identifier ignored_any
+        }
+    }
+
+    impl<'de> IntoDeserializer<'de, Error> for Value {
+        type Deserializer = Self;
+
+        fn into_deserializer(self) -> Self {
+            self
+        }
+    }
+
+    let deserialize = |colors: usize, counts: usize| {
+        let color = || Value::Seq(vec![Value::Int(10), Value::Int(20), Value::Int(30)]);
+        let fields = vec![
+            ("colors", Value::Seq((0..colors).map(|_| color()).collect())),
+            ("counts", Value::Seq((0..counts).map(|_| Value::Int(1)).collect())),
+        ];
+        color_thief::Palette::deserialize(MapDeserializer::<_, Error>::new(fields.into_iter()))
+    };
+
+    let palette = deserialize(2, 2).unwrap();
+    assert_eq!(palette.colors(), &[Color::new(10, 20, 30); 2]);
+    assert_eq!(palette.counts(), &[1, 1]);
+
+    let message = color_thief::Error::InvalidPalette.to_string();
+    assert_eq!(deserialize(0, 0).unwrap_err().to_string(), message);
+    assert_eq!(deserialize(2, 1).unwrap_err().to_string(), message);
+}
+
+#[test]
+fn min_population() {
+    let mut pixels = Vec::new();
+    for i in 0..2000 {
+        pixels.extend_from_slice(match i {
+            0..=999 => &[200, 0, 0],
+            1000..=1989 => &[0, 0, 200],
+            _ => &[0, 200, 0],
+        });
+    }
+
+    let quantizer = |fraction| color_thief::Quantizer::new().quality(1).max_colors(4).min_population(fraction);
+    let colors = quantizer(0.0).build_palette(&pixels, ColorFormat::Rgb).unwrap();
+    assert!(colors.contains(&Color::new(4, 204, 4)));
+
+    let colors = quantizer(0.02).build_palette(&pixels, ColorFormat::Rgb).unwrap();
+    assert_eq!(colors.len(), 2);
+    assert!(!colors.contains(&Color::new(4, 204, 4)));
+
+    let colors = quantizer(0.9).build_palette(&pixels, ColorFormat::Rgb).unwrap();
+    assert_eq!(colors, vec![Color::new(204, 4, 4)]);
+
+    assert_eq!(quantizer(1.5).build_palette(&pixels, ColorFormat::Rgb),
+               Err(color_thief::Error::InvalidMinPopulation));
+}
+
+#[test]
+fn distinct_color_count() {
+    let mut pixels = Vec::new();
+    for color in &[[200, 0, 0], [0, 0, 200], [255, 255, 255], [201, 1, 1]] {
+        for _ in 0..30 {
+            pixels.extend_from_slice(color);
+        }
+    }
+
+    assert_eq!(color_thief::distinct_color_count(&pixels, ColorFormat::Rgb, 1), Ok(2));
+    assert_eq!(color_thief::distinct_color_count(&pixels, ColorFormat::Rgb, 11),
+               Err(color_thief::Error::InvalidQuality));
+
+    let img = image::open(path::Path::new("images/iguana.png")).unwrap();
+    let (buffer, color_type) = get_image_buffer(img);
+    let count = color_thief::distinct_color_count(&buffer, color_type, 10).unwrap();
+    assert!(count > 10 && count <= 1 << 15);
+}
+
+#[test]
+fn is_grayscale() {
+    let mut pixels = Vec::new();
+    for i in 0..1000u32 {
+        let v = (i % 256) as u8;
+        pixels.extend_from_slice(&[v, v.saturating_add(3), v]);
+    }
+
+    assert_eq!(color_thief::is_grayscale(&pixels, ColorFormat::Rgb, 1, 5), Ok(true));
+    assert_eq!(color_thief::is_grayscale(&pixels, ColorFormat::Rgb, 1, 2), Ok(false));
+
+    let img = image::open(path::Path::new("images/iguana.png")).unwrap();
+    let (buffer, color_type) = get_image_buffer(img);
+    assert_eq!(color_thief::is_grayscale(&buffer, color_type, 10, 5), Ok(false));
+    assert_eq!(color_thief::is_grayscale(&[0; 16], ColorFormat::Rgba, 1, 5),
+               Err(color_thief::Error::EmptyImage));
+}
+
+#[test]
+fn quantizer_reuse() {
+    let mut quantizer = color_thief::Quantizer::new();
+    for name in &["images/iguana.png", "images/photo1.jpg", "images/iguana.png"] {
+        let img = image::open(path::Path::new(name)).unwrap();
+        let (buffer, color_type) = get_image_buffer(img);
+        assert_eq!(quantizer.build_palette_reusing(&buffer, color_type),
+                   color_thief::Quantizer::new().build_palette(&buffer, color_type));
+    }
+
+    quantizer.reset();
+    let mut quantizer = quantizer.signal_bits(4);
+    let img = image::open(path::Path::new("images/iguana.png")).unwrap();
+    let (buffer, color_type) = get_image_buffer(img);
+    assert_eq!(quantizer.build_palette_reusing(&buffer, color_type),
+               color_thief::Quantizer::new().signal_bits(4).build_palette(&buffer, color_type));
+}
+
+#[test]
+fn accent_color() {
+    use color_thief::color::accent_color;
+
+    let red = Color::new(200, 30, 30);
+    let light_red = Color::new(230, 120, 120);
+    let teal = Color::new(30, 180, 180);
+    assert_eq!(accent_color(&[red, light_red, teal]), Some(teal));
+    assert_eq!(accent_color(&[red, light_red]), Some(light_red));
+    assert_eq!(accent_color(&[red]), None);
+    assert_eq!(accent_color(&[]), None);
+
+    // Gray colors are chosen by contrast.
+    let gray = Color::new(128, 128, 128);
+    assert_eq!(accent_color(&[gray, Color::new(100, 100, 100), Color::new(250, 250, 250)]),
+               Some(Color::new(250, 250, 250)));
+}
+
+#[test]
+fn planar() {
+    let img = image::open(path::Path::new("images/photo1.jpg")).unwrap();
+    let (buffer, color_type) = get_image_buffer(img);
+    assert_eq!(color_type, ColorFormat::Rgb);
+
+    let plane = |n: usize| -> Vec<u8> { buffer.iter().skip(n).step_by(3).cloned().collect() };
+    let (r, g, b) = (plane(0), plane(1), plane(2));
+    assert_eq!(color_thief::get_palette_planar(&r, &g, &b, 10, 10),
+               color_thief::get_palette(&buffer, color_type, 10, 10));
+    assert_eq!(color_thief::get_palette_planar(&r, &g, &b[1..], 10, 10),
+               Err(color_thief::Error::PlaneLengthMismatch));
+}
+
+#[test]
+fn histogram_builder() {
+    let img = image::open(path::Path::new("images/iguana.png")).unwrap();
+    let (buffer, color_type) = get_image_buffer(img);
+    let expected = color_thief::get_palette(&buffer, color_type, 5, 10).unwrap();
+
+    for &size in &[1, 7, 1000, buffer.len()] {
+        let mut builder = color_thief::HistogramBuilder::new().quality(5);
+        for chunk in buffer.chunks(size) {
+            builder.push_pixels(chunk, color_type).unwrap();
+        }
+
+        assert_eq!(builder.finish().quantize(10).unwrap(), expected);
+    }
+
+    let mut builder = color_thief::HistogramBuilder::new().quality(11);
+    assert_eq!(builder.push_pixels(&buffer, color_type), Err(color_thief::Error::InvalidQuality));
+
+    let mut builder = color_thief::HistogramBuilder::new();
+    builder.push_pixels(&[255; 7], ColorFormat::Rgba).unwrap();
+    assert_eq!(builder.push_pixels(&[0; 3], ColorFormat::Gray),
+               Err(color_thief::Error::ColorFormatMismatch));
+    builder.push_pixels(&[255; 1], ColorFormat::Rgba).unwrap();
+}
+
+#[test]
+fn palette_multi() {
+    let img = image::open(path::Path::new("images/iguana.png")).unwrap();
+    let (iguana, iguana_type) = get_image_buffer(img);
+    let img = image::open(path::Path::new("images/photo1.jpg")).unwrap();
+    let (photo, photo_type) = get_image_buffer(img);
+
+    // A single image is the same as `get_palette`.
+    assert_eq!(color_thief::get_palette_multi(&[(&iguana, iguana_type)], 10, 10),
+               color_thief::get_palette(&iguana, iguana_type, 10, 10));
+
+    let mut histogram = color_thief::Histogram::new();
+    histogram.add_frame(&iguana, iguana_type, 10).unwrap();
+    histogram.add_frame(&photo, photo_type, 10).unwrap();
+    assert_eq!(color_thief::get_palette_multi(&[(&iguana, iguana_type), (&photo, photo_type)], 10, 10),
+               histogram.quantize(10));
+
+    assert_eq!(color_thief::get_palette_multi(&[], 10, 10), Err(color_thief::Error::EmptyImage));
+}
+
+#[test]
+fn progress() {
+    let img = image::open(path::Path::new("images/photo1.jpg")).unwrap();
+    let (buffer, color_type) = get_image_buffer(img);
+    let quantizer = color_thief::Quantizer::new().quality(1);
+
+    let mut values = Vec::new();
+    let colors = quantizer.build_palette_with_progress(&buffer, color_type, &mut |p| {
+        values.push(p);
+        true
+    });
+    assert_eq!(colors, quantizer.build_palette(&buffer, color_type));
+    assert!(values.len() > 1);
+    assert!(values.windows(2).all(|w| w[0] < w[1]));
+    assert_eq!(values.last(), Some(&1.0));
+
+    let mut calls = 0;
+    let colors = quantizer.build_palette_with_progress(&buffer, color_type, &mut |_| {
+        calls += 1;
+        false
+    });
+    assert_eq!(colors, Err(color_thief::Error::Cancelled));
+    assert_eq!(calls, 1);
+}
+
+#[cfg(feature = "css-names")]
+#[test]
+fn nearest_css_name() {
+    use color_thief::nearest_css_name;
+
+    assert_eq!(nearest_css_name(Color::new(255, 0, 0)), "red");
+    assert_eq!(nearest_css_name(Color::new(47, 79, 79)), "darkslategray");
+    assert_eq!(nearest_css_name(Color::new(50, 80, 78)), "darkslategray");
+    assert_eq!(nearest_css_name(Color::new(128, 128, 128)), "gray");
+    assert_eq!(nearest_css_name(Color::new(0, 255, 255)), "aqua");
+    assert_eq!(nearest_css_name(Color::new(1, 1, 1)), "black");
+}
+
+#[cfg(feature = "image")]
+#[test]
+fn dynamic_image() {
+    for name in &["images/iguana.png", "images/photo1.jpg"] {
+        let img = image::open(path::Path::new(name)).unwrap();
+        let colors = color_thief::get_palette_from_dynamic_image(&img, 10, 10);
+        let (buffer, color_type) = get_image_buffer(img);
+        assert_eq!(colors, color_thief::get_palette(&buffer, color_type, 10, 10));
+    }
+
+    let img = image::open(path::Path::new("images/iguana.png")).unwrap();
+    let gray = image::DynamicImage::ImageLuma8(img.to_luma8());
+    let colors = color_thief::get_palette_from_dynamic_image(&gray, 10, 10).unwrap();
+    assert!(colors.iter().all(|c| c.r == c.g && c.g == c.b));
+
+    let rgb16 = image::DynamicImage::ImageRgb16(img.to_rgb16());
+    assert_eq!(color_thief::get_palette_from_dynamic_image(&rgb16, 10, 10),
+               color_thief::get_palette(&img.to_rgb8(), ColorFormat::Rgb, 10, 10));
+}
+
+#[test]
+fn palette_flat() {
+    let img = image::open(path::Path::new("images/photo1.jpg")).unwrap();
+    let (buffer, color_type) = get_image_buffer(img);
+    let colors = color_thief::get_palette(&buffer, color_type, 10, 10).unwrap();
+    let flat = color_thief::get_palette_flat(&buffer, color_type, 10, 10).unwrap();
+    assert_eq!(flat.len(), colors.len() * 3);
+    assert_eq!(&flat[..3], &[colors[0].r, colors[0].g, colors[0].b]);
+    assert_eq!(color_thief::get_palette_flat(&buffer, color_type, 11, 10),
+               Err(color_thief::Error::InvalidQuality));
+}
+
+#[test]
+fn lab() {
+    use color_thief::color::{from_lab, to_lab};
+
+    let (l, a, b) = to_lab(&Color::new(255, 255, 255));
+    assert!((l - 100.0).abs() < 0.01 && a.abs() < 0.01 && b.abs() < 0.01);
+    let (l, a, b) = to_lab(&Color::new(255, 0, 0));
+    assert!((l - 53.24).abs() < 0.01 && (a - 80.09).abs() < 0.01 && (b - 67.2).abs() < 0.01);
+
+    for &c in &[Color::new(0, 0, 0), Color::new(200, 30, 30), Color::new(30, 180, 90), Color::new(12, 34, 250)] {
+        let (l, a, b) = to_lab(&c);
+        assert_eq!(from_lab(l, a, b), c);
+    }
+
+    let mut pixels = Vec::new();
+    for i in 0..2000 {
+        pixels.extend_from_slice(if i < 1000 { &[200, 30, 30] } else { &[30, 180, 90] });
+    }
+
+    let colors = color_thief::Quantizer::new()
+        .quality(1)
+        .max_colors(3)
+        .color_space(color_thief::ColorSpace::Lab)
+        .build_palette(&pixels, ColorFormat::Rgb)
+        .unwrap();
+    // Cells are 8 Lab units wide, which is a small perceptual difference.
+    let close = |a: Color, b: Color| {
+        let (l1, a1, b1) = to_lab(&a);
+        let (l2, a2, b2) = to_lab(&b);
+        (l1 - l2).powi(2) + (a1 - a2).powi(2) + (b1 - b2).powi(2) < 8.0 * 8.0
+    };
+    assert!(colors.iter().any(|&c| close(c, Color::new(200, 30, 30))));
+    assert!(colors.iter().any(|&c| close(c, Color::new(30, 180, 90))));
+}
+
+#[test]
+fn ignore_black() {
+    let mut pixels = Vec::new();
+    for i in 0..2000 {
+        pixels.extend_from_slice(if i < 1000 { &[5, 5, 5] } else { &[200, 30, 30] });
+    }
+
+    let colors = color_thief::get_palette(&pixels, ColorFormat::Rgb, 1, 2).unwrap();
+    assert!(colors.contains(&Color::new(4, 4, 4)));
+
+    let quantizer = color_thief::Quantizer::new().quality(1).max_colors(2).black_threshold(10);
+    let colors = quantizer.build_palette(&pixels, ColorFormat::Rgb).unwrap();
+    assert!(!colors.contains(&Color::new(4, 4, 4)));
+    assert_eq!(colors[0], Color::new(204, 28, 28));
+}
+
+#[test]
+fn invalid_length() {
+    assert_eq!(color_thief::get_palette(&[0; 10], ColorFormat::Rgb, 1, 2),
+               Err(color_thief::Error::InvalidLength));
+    assert_eq!(color_thief::get_palette(&[0; 6], ColorFormat::Rgba, 1, 2),
+               Err(color_thief::Error::InvalidLength));
+    assert_eq!(color_thief::get_palette(&[], ColorFormat::Rgba, 1, 2),
+               Err(color_thief::Error::EmptyImage));
+
+    // Huge dimensions must not overflow.
+    assert_eq!(color_thief::get_background_color(&[0; 12], ColorFormat::Rgb, usize::MAX, usize::MAX, 1),
+               Err(color_thief::Error::EmptyImage));
+    assert_eq!(color_thief::get_palette_with_stride(&[0; 12], ColorFormat::Rgb, usize::MAX, usize::MAX, 1, 2),
+               Err(color_thief::Error::EmptyImage));
+}
+
+#[test]
+fn palette_error() {
+    let img = image::open(path::Path::new("images/photo1.jpg")).unwrap();
+    let (buffer, color_type) = get_image_buffer(img);
+
+    let quantizer = |max_colors| color_thief::Quantizer::new().max_colors(max_colors);
+    let (colors, error4) = quantizer(4).build_palette_with_error(&buffer, color_type).unwrap();
+    assert_eq!(colors, quantizer(4).build_palette(&buffer, color_type).unwrap());
+    let (_, error16) = quantizer(16).build_palette_with_error(&buffer, color_type).unwrap();
+    assert!(error4 > 0.0);
+    assert!(error16 < error4);
+
+    // A two-color image is represented almost exactly.
+    let mut pixels = Vec::new();
+    for i in 0..1000 {
+        pixels.extend_from_slice(if i < 500 { &[204, 20, 20] } else { &[20, 20, 204] });
+    }
+
+    let (_, error) = quantizer(3).quality(1).build_palette_with_error(&pixels, ColorFormat::Rgb).unwrap();
+    assert_eq!(error, 0.0);
+}
+
+#[test]
+fn quantize_debug() {
+    let img = image::open(path::Path::new("images/iguana.png")).unwrap();
+    let (buffer, color_type) = get_image_buffer(img);
+
+    let mut histogram = color_thief::Histogram::new();
+    histogram.add_frame(&buffer, color_type, 10).unwrap();
+    let boxes = histogram.quantize_debug(10).unwrap();
+    let colors: Vec<_> = boxes.iter().map(|b| b.average).collect();
+    assert_eq!(colors, histogram.quantize(10).unwrap());
+
+    let (min, max) = histogram.bounds().unwrap();
+    for b in &boxes {
+        let volume: u32 = (0..3).map(|i| (b.max[i] as u32 + 1).saturating_sub(b.min[i] as u32)).product();
+        assert_eq!(b.volume, volume);
+        assert!((0..3).all(|i| b.min[i] >= min[i] && b.max[i] <= max[i]));
+    }
+
+    let total: u64 = boxes.iter().map(|b| b.count).sum();
+    assert_eq!(total, histogram.counts().iter().sum::<u64>());
+}
+
+#[test]
+fn hue_histogram() {
+    let mut pixels = Vec::new();
+    for i in 0..3000 {
+        pixels.extend_from_slice(match i {
+            0..=999 => &[200, 0, 0],
+            1000..=1999 => &[0, 0, 200],
+            _ => &[100, 100, 100],
+        });
+    }
+
+    let histogram = color_thief::hue_histogram(&pixels, ColorFormat::Rgb, 1, 6).unwrap();
+    assert_eq!(histogram.len(), 6);
+    // Red and blue equally, gray is ignored.
+    assert!((histogram[0] - 0.5).abs() < 0.01);
+    assert!((histogram[4] - 0.5).abs() < 0.01);
+    assert!((histogram.iter().sum::<f32>() - 1.0).abs() < 0.001);
+
+    let gray = [100; 300];
+    assert_eq!(color_thief::hue_histogram(&gray, ColorFormat::Rgb, 1, 4), Ok(vec![0.0; 4]));
+    assert_eq!(color_thief::hue_histogram(&gray, ColorFormat::Rgb, 1, 0), Ok(vec![]));
+}
+
+#[test]
+fn vividness() {
+    let gray = [100; 300];
+    assert_eq!(color_thief::vividness(&gray, ColorFormat::Rgb, 1), Ok(0.0));
+
+    let mut pixels = Vec::new();
+    for i in 0..1000 {
+        pixels.extend_from_slice(if i < 100 { &[255, 0, 0] } else { &[100, 100, 100] });
+    }
+    assert_eq!(color_thief::vividness(&pixels, ColorFormat::Rgb, 1), Ok(1.0));
+
+    let muted = color_thief::vividness(&[150, 100, 100].repeat(100), ColorFormat::Rgb, 1).unwrap();
+    assert!(muted > 0.0 && muted < 0.5);
+
+    // Quality 1 samples every 3rd RGB pixel: red, muted red, red, ...
+    let mut pixels = Vec::new();
+    for i in 0..600 {
+        pixels.extend_from_slice(match i % 6 {
+            0 => &[255, 0, 0],
+            3 => &[150, 100, 100],
+            _ => &[100, 100, 150],
+        });
+    }
+    let (_, s, _) = color_thief::color::to_hsl(&Color::new(150, 100, 100));
+    let (mut weighted, mut total) = (0.0f64, 0.0f64);
+    for _ in 0..100 {
+        weighted += 1.0;
+        total += 1.0;
+        weighted += (s * s) as f64;
+        total += s as f64;
+    }
+    assert_eq!(color_thief::vividness(&pixels, ColorFormat::Rgb, 1), Ok((weighted / total) as f32));
+
+    let img = image::open(path::Path::new("images/photo1.jpg")).unwrap();
+    let (buffer, color_type) = get_image_buffer(img);
+    let score = color_thief::vividness(&buffer, color_type, 10).unwrap();
+    assert!(score > 0.0 && score < 1.0);
+}
+
+#[test]
+fn palette_entropy() {
+    use color_thief::color::palette_entropy;
+
+    let red = Color::new(255, 0, 0);
+    let blue = Color::new(0, 0, 255);
+    assert_eq!(palette_entropy(&[]), 0.0);
+    assert_eq!(palette_entropy(&[(red, 10)]), 0.0);
+    assert_eq!(palette_entropy(&[(red, 10), (blue, 0)]), 0.0);
+    assert!((palette_entropy(&[(red, 5), (blue, 5)]) - 1.0).abs() < 1e-6);
+
+    let colors: Vec<_> = (0..8).map(|i| (Color::new(i * 30, 0, 0), 3)).collect();
+    assert!((palette_entropy(&colors) - 3.0).abs() < 1e-6);
+
+    let skewed = palette_entropy(&[(red, 9), (blue, 1)]);
+    assert!((skewed - 0.469).abs() < 1e-3);
+
+    let img = image::open(path::Path::new("images/photo1.jpg")).unwrap();
+    let (buffer, color_type) = get_image_buffer(img);
+    let colors = color_thief::get_palette_with_counts(&buffer, color_type, 10, 10).unwrap();
+    let entropy = palette_entropy(&colors);
+    assert!(entropy > 0.0 && entropy <= (colors.len() as f32).log2());
+}
+
+#[test]
+fn color_temperature() {
+    let gray = [100; 300];
+    assert_eq!(color_thief::color_temperature(&gray, ColorFormat::Rgb, 1), Ok(0.0));
+
+    let orange = [255, 128, 0].repeat(100);
+    assert!(color_thief::color_temperature(&orange, ColorFormat::Rgb, 1).unwrap() > 0.9);
+
+    let blue = [0, 128, 255].repeat(100);
+    assert!(color_thief::color_temperature(&blue, ColorFormat::Rgb, 1).unwrap() < -0.9);
+
+    // Green is neither warm nor cool.
+    let green = [0, 255, 0].repeat(100);
+    let score = color_thief::color_temperature(&green, ColorFormat::Rgb, 1).unwrap();
+    assert!(score.abs() < 0.1);
+
+    let muted = color_thief::color_temperature(&[150, 100, 100].repeat(100), ColorFormat::Rgb, 1).unwrap();
+    assert!(muted > 0.0 && muted < 0.5);
+}
+
+#[test]
+fn gradient() {
+    use color_thief::color::gradient;
+
+    let black = Color::new(0, 0, 0);
+    let white = Color::new(255, 255, 255);
+    let red = Color::new(255, 0, 0);
+
+    let colors = gradient(&[white, black], 3);
+    assert_eq!(colors, vec![black, Color::new(188, 188, 188), white]);
+
+    let colors = gradient(&[white, red, black], 5);
+    assert_eq!(colors.len(), 5);
+    assert_eq!(colors[0], black);
+    assert_eq!(colors[2], red);
+    assert_eq!(colors[4], white);
+
+    assert_eq!(gradient(&[red], 2), vec![red, red]);
+    assert_eq!(gradient(&[red, white], 1), vec![red]);
+    assert_eq!(gradient(&[], 3), vec![]);
+}
+
+#[test]
+fn custom_extractor() {
+    let img = image::open(path::Path::new("images/photo1.jpg")).unwrap();
+    let (buffer, color_type) = get_image_buffer(img);
+    assert_eq!(color_type, ColorFormat::Rgb);
+
+    let rgb = |p: &[u8], pos: usize| (p[pos], p[pos + 1], p[pos + 2], 255);
+    assert_eq!(color_thief::get_palette_with(&buffer, 3, rgb, 10, 10),
+               color_thief::get_palette(&buffer, color_type, 10, 10));
+
+    // RGB565, little endian.
+    let mut pixels = Vec::new();
+    for i in 0..1000u32 {
+        let c: u16 = if i < 500 { 0xF800 } else { 0x001F };
+        pixels.extend_from_slice(&c.to_le_bytes());
+    }
+
+    let rgb565 = |p: &[u8], pos: usize| {
+        let c = u16::from_le_bytes([p[pos], p[pos + 1]]);
+        (((c >> 11) << 3) as u8, (((c >> 5) & 0x3F) << 2) as u8, ((c & 0x1F) << 3) as u8, 255)
+    };
+    let colors = color_thief::get_palette_with(&pixels, 2, rgb565, 1, 2).unwrap();
+    assert!(colors.contains(&Color::new(252, 4, 4)));
+    assert!(colors.contains(&Color::new(4, 4, 252)));
+
+    assert_eq!(color_thief::get_palette_with(&pixels[1..], 2, rgb565, 1, 2),
+               Err(color_thief::Error::InvalidLength));
+}
+
+#[test]
+fn alpha_weighted() {
+    let mut pixels = Vec::new();
+    for i in 0..1000 {
+        pixels.extend_from_slice(if i < 500 { &[200, 20, 20, 255] } else { &[20, 20, 200, 100] });
+    }
+
+    let quantizer = color_thief::Quantizer::new().quality(1).max_colors(2);
+    let colors = quantizer.build_palette(&pixels, ColorFormat::Rgba).unwrap();
+    assert!(!colors.contains(&Color::new(20, 20, 204)));
+
+    let palette = color_thief::Quantizer::new()
+        .quality(1)
+        .max_colors(2)
+        .alpha_mode(color_thief::AlphaMode::Weighted)
+        .build(&pixels, ColorFormat::Rgba)
+        .unwrap();
+    let red = palette.iter().position(|&c| c == Color::new(204, 20, 20)).unwrap();
+    let blue = palette.iter().position(|&c| c == Color::new(20, 20, 204)).unwrap();
+    // Every 4th pixel is sampled. Counts are in pixels, blue ones are 100 / 255 opaque.
+    assert_eq!(palette.counts()[red], 125);
+    assert_eq!(palette.counts()[blue], 49);
+
+    // Nearly transparent pixels still count.
+    let faint = [20, 20, 200, 1].repeat(100);
+    let palette = color_thief::Quantizer::new()
+        .quality(1)
+        .max_colors(2)
+        .alpha_mode(color_thief::AlphaMode::Weighted)
+        .build(&faint, ColorFormat::Rgba)
+        .unwrap();
+    assert_eq!(palette.colors()[0], Color::new(20, 20, 204));
+}
+
+#[test]
+fn palette_f32() {
+    assert_eq!(color_thief::color::to_f32(&Color::new(255, 0, 51)), [1.0, 0.0, 0.2]);
+
+    let img = image::open(path::Path::new("images/photo1.jpg")).unwrap();
+    let (buffer, color_type) = get_image_buffer(img);
+    let colors = color_thief::get_palette(&buffer, color_type, 10, 10).unwrap();
+    let floats = color_thief::get_palette_f32(&buffer, color_type, 10, 10).unwrap();
+    assert_eq!(floats.len(), colors.len());
+    assert_eq!(floats[0], color_thief::color::to_f32(&colors[0]));
+}
+
+#[test]
+fn no_visible_pixels() {
+    let buffer = [200, 20, 20, 0].repeat(100);
+    assert_eq!(color_thief::get_palette(&buffer, ColorFormat::Rgba, 1, 2),
+               Err(color_thief::Error::NoVisiblePixels));
+
+    let mut histogram = color_thief::Histogram::new();
+    histogram.add_frame(&buffer, ColorFormat::Rgba, 1).unwrap();
+    assert_eq!(histogram.quantize(2), Err(color_thief::Error::NoVisiblePixels));
+    histogram.clear();
+    assert_eq!(histogram.quantize(2), Err(color_thief::Error::EmptyImage));
+}
+
+#[test]
+fn simd_matches_scalar() {
+    // Random colors with runs of transparent, white and black pixels.
+    let mut seed = 1u32;
+    let mut buffer = Vec::new();
+    for i in 0..4099 {
+        seed = seed.wrapping_mul(1664525).wrapping_add(1013904223);
+        let [r, g, b, a] = seed.to_le_bytes();
+        match i / 7 % 5 {
+            0 => buffer.extend_from_slice(&[r, g, b, a / 2]),
+            1 => buffer.extend_from_slice(&[255, 254, 253, 255]),
+            2 => buffer.extend_from_slice(&[r / 32, g / 32, b / 32, 255]),
+            _ => buffer.extend_from_slice(&[r, g, b, a]),
+        }
+    }
+
+    // `snap_exact` doesn't change counts, but is not supported by the `simd` feature.
+    let check = |pixels: &[u8], color_format: ColorFormat, quality: u8, options: color_thief::Options| {
+        let mut fast = color_thief::Histogram::with_options(options).unwrap();
+        let mut slow = color_thief::Histogram::with_options(
+            color_thief::Options { snap_exact: true, ..options }).unwrap();
+        fast.add_frame(pixels, color_format, quality).unwrap();
+        slow.add_frame(pixels, color_format, quality).unwrap();
+        assert_eq!(fast.counts(), slow.counts());
+        assert_eq!(fast.bounds(), slow.bounds());
+        (fast, slow)
+    };
+
+    let defaults = color_thief::Options::default();
+    for &color_format in &[ColorFormat::Rgba, ColorFormat::Rgb, ColorFormat::Argb, ColorFormat::Gray] {
+        for &quality in &[0, 1, 3, 10] {
+            for len in (0..buffer.len()).step_by(2999) {
+                let pixels = &buffer[..len - len % color_format.channels()];
+                check(pixels, color_format, quality, defaults);
+                check(pixels, color_format, quality, color_thief::Options {
+                    signal_bits: 6,
+                    ignore_white: None,
+                    ignore_black: Some(10),
+                    alpha_threshold: 200,
+                    ..defaults
+                });
+                check(pixels, color_format, quality, color_thief::Options {
+                    signal_bits: 3,
+                    sampling: Sampling::Random { seed: len as u64 },
+                    ..defaults
+                });
+            }
+        }
+    }
+
+    // Only skipped pixels.
+    let (fast, slow) = check(&[255, 255, 255, 255].repeat(50), ColorFormat::Rgba, 1, defaults);
+    assert_eq!(fast.quantize(5), Ok(vec![Color::new(255, 255, 255)]));
+    assert_eq!(slow.quantize(5), Ok(vec![Color::new(255, 255, 255)]));
+    let (fast, slow) = check(&[0, 0, 0, 0].repeat(50), ColorFormat::Rgba, 1, defaults);
+    assert_eq!(fast.quantize(5), Err(color_thief::Error::NoVisiblePixels));
+    assert_eq!(slow.quantize(5), Err(color_thief::Error::NoVisiblePixels));
+}